@@ -0,0 +1,82 @@
+//! Who wrote the commits of a repo, counted by `scan --authors`.
+//!
+//! Every commit reachable from a local branch is attributed to its author email. Walking the whole
+//! history is too slow for every scan, so the counts are only refreshed when asked for.
+
+use anyhow::Result;
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How many authors `top_authors` keeps
+const TOP: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthorCommits {
+    pub name: String,
+    /// Lower case
+    pub email: String,
+    pub commits: usize,
+}
+
+/// The authors with the most commits, most first, and how many commits were made with the
+/// `user.email` git uses in the repo
+pub fn count(repo: &Repository) -> Result<(Vec<AuthorCommits>, usize)> {
+    let mut walk = repo.revwalk()?;
+    walk.push_glob("refs/heads")?;
+
+    let mut by_email = HashMap::<String, AuthorCommits>::new();
+    for id in walk {
+        let commit = repo.find_commit(id?)?;
+        let author = commit.author();
+        let email = author.email().unwrap_or_default().to_lowercase();
+        by_email
+            .entry(email.clone())
+            .or_insert_with(|| AuthorCommits {
+                name: author.name().unwrap_or_default().to_owned(),
+                email,
+                commits: 0,
+            })
+            .commits += 1;
+    }
+
+    let mine = repo
+        .config()
+        .and_then(|c| c.get_string("user.email"))
+        .ok()
+        .and_then(|email| by_email.get(&email.to_lowercase()))
+        .map_or(0, |a| a.commits);
+
+    Ok((top(by_email.into_values().collect()), mine))
+}
+
+/// The `TOP` authors with the most commits, ties in name order
+fn top(mut authors: Vec<AuthorCommits>) -> Vec<AuthorCommits> {
+    authors.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.name.cmp(&b.name)));
+    authors.truncate(TOP);
+    authors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top() {
+        let author = |name: &str, commits| AuthorCommits {
+            name: name.to_owned(),
+            email: format!("{}@example.com", name.to_lowercase()),
+            commits,
+        };
+        let authors = vec![
+            author("Carol", 3),
+            author("Alice", 10),
+            author("Bob", 3),
+            author("Dan", 1),
+            author("Eve", 2),
+            author("Frank", 1),
+        ];
+        let names = top(authors).into_iter().map(|a| a.name).collect::<Vec<_>>();
+        assert_eq!(names, ["Alice", "Bob", "Carol", "Eve", "Dan"]);
+    }
+}
@@ -0,0 +1,176 @@
+//! `git bundle` backups of the cached repos, and restoring them as clones.
+//!
+//! A backup directory holds one `<n>-<name>.bundle` per repo with every branch and tag, plus
+//! `manifest.json` recording where each repo lived, its remotes and its checked out branch.
+
+use crate::{canonical_path, parallel_map, run_git, Cache};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub created: String,
+    pub repos: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupEntry {
+    pub path: String,
+    /// File name inside the backup directory
+    pub bundle: String,
+    /// Fetch url by remote name
+    pub remotes: BTreeMap<String, String>,
+    pub branch: Option<String>,
+}
+
+/// Bundles every repo of `data` into `dest`, returning the ones that failed with their error.
+/// Repos without commits can't be bundled.
+pub fn backup(data: &Cache, dest: &Path, jobs: usize) -> Result<Vec<(String, anyhow::Error)>> {
+    fs::create_dir_all(dest).with_context(|| format!("Could not create {dest:?}"))?;
+    let dest = canonical_path(dest)?;
+
+    let numbered = data.iter().enumerate().collect::<Vec<_>>();
+    let results = parallel_map(&numbered, jobs, |(i, e)| {
+        let name = Path::new(&e.path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let bundle = format!("{i:04}-{name}.bundle");
+        let file = dest.join(&bundle);
+        let file = file.to_str().context("Backup path is not valid UTF-8")?;
+
+        let path = Path::new(&e.path);
+        run_git(path, &["bundle", "create", file, "--all"])?;
+        Ok(BackupEntry {
+            path: e.path.clone(),
+            bundle,
+            remotes: remotes(path)?,
+            branch: e.current_branch.clone(),
+        })
+    });
+
+    let mut repos = Vec::new();
+    let mut failed = Vec::new();
+    for ((_, e), res) in numbered.iter().zip(results) {
+        match res {
+            Ok(entry) => repos.push(entry),
+            Err(err) => failed.push((e.path.clone(), err)),
+        }
+    }
+
+    let manifest = Manifest {
+        created: Utc::now().to_rfc3339(),
+        repos,
+    };
+    fs::write(
+        dest.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    Ok(failed)
+}
+
+/// `git remote -v` as name to fetch url
+fn remotes(path: &Path) -> Result<BTreeMap<String, String>> {
+    let out = run_git(path, &["remote", "-v"])?;
+    Ok(out
+        .lines()
+        .filter_map(|l| l.strip_suffix(" (fetch)"))
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(name, url)| (name.to_owned(), url.to_owned()))
+        .collect())
+}
+
+pub fn load_manifest(src: &Path) -> Result<Manifest> {
+    let path = src.join("manifest.json");
+    let text = fs::read_to_string(&path).with_context(|| format!("Could not read {path:?}"))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid manifest {path:?}"))
+}
+
+/// Where a backed up repo goes: its old path, or a directory of the same name inside `into`
+pub fn target(entry: &BackupEntry, into: Option<&Path>) -> PathBuf {
+    let path = PathBuf::from(&entry.path);
+    match (into, path.file_name()) {
+        (Some(into), Some(name)) => into.join(name),
+        _ => path,
+    }
+}
+
+/// Clones the bundle to `target` with all its branches, then points the remotes back at the
+/// urls the repo had
+pub fn restore(src: &Path, entry: &BackupEntry, target: &Path) -> Result<()> {
+    if target.exists() {
+        bail!("{} already exists", target.display());
+    }
+    // absolute, git runs in the new clone for the fetch
+    let bundle = canonical_path(&src.join(&entry.bundle))?;
+    let bundle = bundle.to_str().context("Backup path is not valid UTF-8")?;
+    let target_str = target.to_str().context("Target path is not valid UTF-8")?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    run_git(
+        Path::new("."),
+        &["clone", "--no-checkout", bundle, target_str],
+    )?;
+    // a clone only creates the branch HEAD points at, the others would be left as origin/*
+    run_git(
+        target,
+        &[
+            "fetch",
+            "--update-head-ok",
+            bundle,
+            "+refs/heads/*:refs/heads/*",
+        ],
+    )?;
+    for tracking in run_git(
+        target,
+        &["for-each-ref", "--format=%(refname)", "refs/remotes/origin"],
+    )?
+    .lines()
+    {
+        run_git(target, &["update-ref", "-d", tracking])?;
+    }
+
+    match entry.remotes.get("origin") {
+        Some(url) => run_git(target, &["remote", "set-url", "origin", url])?,
+        None => run_git(target, &["remote", "remove", "origin"])?,
+    };
+    for (name, url) in entry.remotes.iter().filter(|(name, _)| *name != "origin") {
+        run_git(target, &["remote", "add", name, url])?;
+    }
+
+    match &entry.branch {
+        Some(branch) => run_git(target, &["checkout", "-f", branch])?,
+        None => run_git(target, &["reset", "--hard"])?,
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        let entry = BackupEntry {
+            path: "/home/me/src/pplaces".to_owned(),
+            bundle: "0000-pplaces.bundle".to_owned(),
+            remotes: BTreeMap::new(),
+            branch: Some("main".to_owned()),
+        };
+        assert_eq!(target(&entry, None), PathBuf::from("/home/me/src/pplaces"));
+        assert_eq!(
+            target(&entry, Some(Path::new("/restored"))),
+            PathBuf::from("/restored/pplaces")
+        );
+    }
+}
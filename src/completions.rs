@@ -0,0 +1,194 @@
+//! Shell completion scripts and the `pj` shell function.
+//!
+//! clap_complete generates the subcommands and flags. On top of that every script asks
+//! `pplaces complete-repos` for words from the cache: repo paths for `--under`, repo names for
+//! `--matching` and `jump`, and the remotes of other machines (see sync-cache) for `clone`.
+
+use crate::{sync, Cache};
+use anyhow::Result;
+use clap::{ArgEnum, IntoApp};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// What `complete-repos` prints
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+pub enum RepoWords {
+    /// Last component of every cached repo path
+    Names,
+    /// Full paths of the cached repos
+    Paths,
+    /// Remotes cached on other machines, things that could be cloned here
+    Remotes,
+}
+
+const BASH: &str = r#"
+_pplaces_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" words=""
+    case "$prev" in
+        --under) words="$(pplaces complete-repos paths 2>/dev/null)" ;;
+        --matching) words="$(pplaces complete-repos names 2>/dev/null)" ;;
+        *)
+            if [[ "$cur" != -* && $COMP_CWORD -gt 1 ]]; then
+                case "${COMP_WORDS[1]}" in
+                    clone) words="$(pplaces complete-repos remotes 2>/dev/null)" ;;
+                    jump) words="$(pplaces complete-repos names 2>/dev/null)" ;;
+                esac
+            fi
+            ;;
+    esac
+    if [[ -n "$words" ]]; then
+        COMPREPLY=($(compgen -W "$words" -- "$cur"))
+        return 0
+    fi
+    _pplaces "$@"
+}
+complete -F _pplaces_dynamic -o bashdefault -o default pplaces
+"#;
+
+const ZSH: &str = r#"
+_pplaces_dynamic() {
+    local -a candidates
+    case "${words[CURRENT-1]}" in
+        --under) candidates=(${(f)"$(pplaces complete-repos paths 2>/dev/null)"}) ;;
+        --matching) candidates=(${(f)"$(pplaces complete-repos names 2>/dev/null)"}) ;;
+        *)
+            if [[ "${words[CURRENT]}" != -* && $CURRENT -gt 2 ]]; then
+                case "${words[2]}" in
+                    clone) candidates=(${(f)"$(pplaces complete-repos remotes 2>/dev/null)"}) ;;
+                    jump) candidates=(${(f)"$(pplaces complete-repos names 2>/dev/null)"}) ;;
+                esac
+            fi
+            ;;
+    esac
+    if (( ${#candidates} )); then
+        compadd -a candidates
+    else
+        _pplaces "$@"
+    fi
+}
+compdef _pplaces_dynamic pplaces
+"#;
+
+const FISH: &str = r#"
+complete -c pplaces -l under -x -a "(pplaces complete-repos paths 2>/dev/null)"
+complete -c pplaces -l matching -x -a "(pplaces complete-repos names 2>/dev/null)"
+complete -c pplaces -n "__fish_seen_subcommand_from clone" -x -a "(pplaces complete-repos remotes 2>/dev/null)"
+complete -c pplaces -n "__fish_seen_subcommand_from jump" -x -a "(pplaces complete-repos names 2>/dev/null)"
+"#;
+
+const BASH_INIT: &str = r#"pj() {
+    local dir
+    dir="$(pplaces jump "$*")" && cd "$dir"
+}
+_pj() {
+    COMPREPLY=($(compgen -W "$(pplaces complete-repos names 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+}
+complete -F _pj pj
+"#;
+
+const ZSH_INIT: &str = r#"pj() {
+    local dir
+    dir="$(pplaces jump "$*")" && cd "$dir"
+}
+_pj() {
+    compadd ${(f)"$(pplaces complete-repos names 2>/dev/null)"}
+}
+(( $+functions[compdef] )) && compdef _pj pj
+"#;
+
+const FISH_INIT: &str = r#"function pj
+    set -l dir (pplaces jump "$argv"); and cd $dir
+end
+complete -c pj -x -a "(pplaces complete-repos names 2>/dev/null)"
+"#;
+
+/// The completion script for `shell`, meant to be sourced
+pub fn script(shell: CompletionShell) -> String {
+    let mut app = crate::CliArgs::into_app();
+    let mut out = Vec::new();
+    let extra = match shell {
+        CompletionShell::Bash => {
+            clap_complete::generate(clap_complete::Shell::Bash, &mut app, "pplaces", &mut out);
+            BASH
+        }
+        CompletionShell::Zsh => {
+            clap_complete::generate(clap_complete::Shell::Zsh, &mut app, "pplaces", &mut out);
+            ZSH
+        }
+        CompletionShell::Fish => {
+            clap_complete::generate(clap_complete::Shell::Fish, &mut app, "pplaces", &mut out);
+            FISH
+        }
+    };
+
+    let mut script = String::from_utf8_lossy(&out).into_owned();
+    // the zsh script calls itself at the end, which only works when autoloaded from fpath
+    if let Some(body) = script.trim_end().strip_suffix("_pplaces \"$@\"") {
+        script = body.to_owned();
+    }
+    script.push_str(extra);
+
+    script
+}
+
+/// Defines `pj <query>`, which cds into the repo `jump` picks, e.g. `eval "$(pplaces init bash)"`
+pub fn init_script(shell: CompletionShell) -> &'static str {
+    match shell {
+        CompletionShell::Bash => BASH_INIT,
+        CompletionShell::Zsh => ZSH_INIT,
+        CompletionShell::Fish => FISH_INIT,
+    }
+}
+
+/// Words for the dynamic part of the completion scripts, one per line
+pub fn repo_words(data: &Cache, kind: RepoWords) -> Result<Vec<String>> {
+    let mut words = match kind {
+        RepoWords::Names => data
+            .iter()
+            .filter_map(|e| Path::new(&e.path).file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .collect(),
+        RepoWords::Paths => data.iter().map(|e| e.path.clone()).collect(),
+        RepoWords::Remotes => {
+            let mut remotes = Vec::new();
+            for machine in sync::machine_names()? {
+                for e in sync::load_machine(&machine)? {
+                    remotes.extend(
+                        e.upstream
+                            .iter()
+                            .filter_map(|u| u.strip_suffix(" (fetch)"))
+                            .map(|u| u.to_owned()),
+                    );
+                }
+            }
+            remotes
+        }
+    };
+    words.sort();
+    words.dedup();
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let bash = script(CompletionShell::Bash);
+        assert!(bash.contains("_pplaces()"));
+        assert!(bash.ends_with("complete -F _pplaces_dynamic -o bashdefault -o default pplaces\n"));
+
+        let zsh = script(CompletionShell::Zsh);
+        assert!(zsh.starts_with("#compdef pplaces"));
+        assert!(zsh.contains("compdef _pplaces_dynamic pplaces"));
+    }
+}
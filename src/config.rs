@@ -0,0 +1,373 @@
+//! User settings from `config.toml` in the config dir.
+//!
+//! ```toml
+//! roots = ["~/src", "~/work"]
+//! days_to_show = 30
+//! exclude = ["node_modules", "target", ".cache*", "/mnt/*"]
+//! clone_layout = "~/src/{host}/{owner}/{repo}"
+//! jobs = 4
+//! nice = 10
+//! io_nice = "idle"
+//! forge = "gitlab"
+//! forge_host = "gitlab.example.com"
+//! trash_days = 30
+//!
+//! [tokens]
+//! "gitlab.example.com" = "glpat-..."
+//!
+//! [workspaces.work]
+//! tags = ["work"]
+//! paths = ["~/work"]
+//!
+//! [policies."/mnt/nas"]
+//! max_depth = 2
+//! sizes = false
+//! every_days = 7
+//!
+//! [hooks]
+//! post-clone = "notify-send \"Cloned $PPLACES_REPO_NAME\""
+//!
+//! [theme]
+//! preset = "high-contrast"
+//! dirty = "bold magenta"
+//! ```
+
+use crate::{canonical_path, forge::Forge, ScanOpts};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// What `scan` looks at when no path is given
+    pub roots: Vec<String>,
+    /// Default for `--days-to-show`
+    pub days_to_show: Option<u32>,
+    /// Directories never descended into while scanning, `*` matches anything. Patterns with a
+    /// `/` match the whole path, the others the directory name
+    pub exclude: Vec<String>,
+    /// Where `clone` puts a repo when no directory is given
+    pub clone_layout: Option<String>,
+    /// Defaults for `--jobs`, `--nice` and `--io-nice` of the commands working on many repos
+    pub jobs: Option<usize>,
+    pub nice: Option<i32>,
+    pub io_nice: Option<String>,
+    /// Default for `upload --forge`
+    pub forge: Option<Forge>,
+    /// Default for `upload --host`
+    pub forge_host: Option<String>,
+    /// How many days `remove` keeps repos in the trash for `undo`, 30 without it
+    pub trash_days: Option<u32>,
+    /// API tokens by forge host, the environment variables take precedence
+    pub tokens: HashMap<String, String>,
+    /// Named sets of repos for `show --workspace`
+    pub workspaces: HashMap<String, Workspace>,
+    /// How the roots under each path are scanned and watched, the longest matching path wins
+    pub policies: HashMap<String, RootPolicy>,
+    /// Commands run after `scan`, `clone`, `upload` and `remove`
+    pub hooks: Hooks,
+    /// Colors of `show --table` and `heatmap`
+    pub theme: ThemeConfig,
+}
+
+/// Shell commands run on each event, see the `hooks` module of the binary for what they get
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Hooks {
+    pub post_scan: Option<String>,
+    pub post_clone: Option<String>,
+    pub post_upload: Option<String>,
+    pub repo_removed: Option<String>,
+}
+
+/// A preset and the styles that differ from it. A style is a color name like `dark_grey`, with
+/// `bold`, `italic` or `underlined` before it if wanted, or `none`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub preset: ThemePreset,
+    /// Column titles
+    pub header: Option<String>,
+    pub path: Option<String>,
+    /// Age of the last commit: less than a week, less than three months, older
+    pub age_fresh: Option<String>,
+    pub age_recent: Option<String>,
+    pub age_old: Option<String>,
+    pub branch: Option<String>,
+    /// The `*` of repos with uncommitted changes
+    pub dirty: Option<String>,
+    pub remote: Option<String>,
+    /// Days with commits in `heatmap`, and those without
+    pub heat: Option<String>,
+    pub heat_empty: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    #[default]
+    Default,
+    /// Bold blue and yellow, which stay apart with red-green color blindness and on dim screens
+    HighContrast,
+    /// Bold and plain text only
+    Monochrome,
+}
+
+/// Repos carrying any of `tags` or living under any of `paths`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Workspace {
+    pub tags: Vec<String>,
+    pub paths: Vec<String>,
+}
+
+/// Scan settings of a root, on top of the command line ones
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RootPolicy {
+    /// Used when `--max-depth` isn't given
+    pub max_depth: Option<usize>,
+    /// Added to `exclude` and `--exclude`
+    pub exclude: Vec<String>,
+    /// Read the size of the repos, which goes through every file in them
+    pub sizes: bool,
+    /// Read uncommitted changes, stashes and the commits not pushed or pulled yet
+    pub status: bool,
+    /// A `scan` of every root leaves this one out when it was scanned less than this many days ago
+    pub every_days: Option<u32>,
+    /// Whether `watch` looks at it
+    pub watch: bool,
+}
+
+impl Default for RootPolicy {
+    fn default() -> Self {
+        RootPolicy {
+            max_depth: None,
+            exclude: Vec::new(),
+            sizes: true,
+            status: true,
+            every_days: None,
+            watch: true,
+        }
+    }
+}
+
+impl RootPolicy {
+    /// `opts` with this policy applied, what was given on the command line wins
+    pub fn apply(&self, opts: &ScanOpts) -> ScanOpts {
+        ScanOpts {
+            max_depth: opts.max_depth.or(self.max_depth),
+            exclude: opts
+                .exclude
+                .iter()
+                .cloned()
+                .chain(self.exclude.iter().map(|p| expand_pattern(p)))
+                .collect(),
+            no_sizes: opts.no_sizes || !self.sizes,
+            no_status: opts.no_status || !self.status,
+            ..opts.clone()
+        }
+    }
+}
+
+impl Workspace {
+    pub fn contains(&self, repo: &Path, tags: &[String]) -> bool {
+        self.tags.iter().any(|t| tags.contains(t))
+            || self.paths.iter().any(|p| repo.starts_with(expand_home(p)))
+    }
+}
+
+impl Config {
+    pub fn roots(&self) -> Vec<PathBuf> {
+        self.roots.iter().map(|r| expand_home(r)).collect()
+    }
+
+    /// Policy of the path of `policies` that `root` is in, the longest one when there are several
+    pub fn policy(&self, root: &Path) -> RootPolicy {
+        self.policies
+            .iter()
+            .map(|(path, policy)| {
+                let path = expand_home(path);
+                (canonical_path(&path).unwrap_or(path), policy)
+            })
+            .filter(|(path, _)| root.starts_with(path))
+            .max_by_key(|(path, _)| path.components().count())
+            .map(|(_, policy)| policy.clone())
+            .unwrap_or_default()
+    }
+
+    /// `exclude` followed by `extra`, with `~/` expanded in path patterns
+    pub fn excludes(&self, extra: &[String]) -> Vec<String> {
+        self.exclude
+            .iter()
+            .chain(extra)
+            .map(|p| expand_pattern(p))
+            .collect()
+    }
+
+    /// Clone destination for `clone --layout`: `clone_layout` when set, otherwise
+    /// `<root>/<host>/<owner>/<repo>` under the first root or `~/src`
+    pub fn layout_path(&self, host: &str, owner_repo: &str) -> Option<PathBuf> {
+        if self.clone_layout.is_some() {
+            return self.clone_path(host, owner_repo);
+        }
+        Some(join_slashed(self.default_root().join(host), owner_repo))
+    }
+
+    /// Where new repos go: the first root, or `~/src` without any
+    pub fn default_root(&self) -> PathBuf {
+        self.roots()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| expand_home("~/src"))
+    }
+
+    /// Clone destination of `host/owner/repo` following `clone_layout`, `{owner}` takes the
+    /// subgroups of GitLab along
+    pub fn clone_path(&self, host: &str, owner_repo: &str) -> Option<PathBuf> {
+        let (owner, repo) = owner_repo.rsplit_once('/')?;
+        let path = self
+            .clone_layout
+            .as_ref()?
+            .replace("{host}", host)
+            .replace("{owner}", owner)
+            .replace("{repo}", repo);
+        Some(expand_home(&path))
+    }
+}
+
+/// Reads the config file, a missing one is the same as an empty one.
+pub fn load() -> Result<Config> {
+    let path = match crate::config_dir() {
+        Some(dir) => dir.join("config.toml"),
+        None => return Ok(Config::default()),
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Ok(Config::default()),
+    };
+
+    toml::from_str(&text).with_context(|| format!("Invalid config in {path:?}"))
+}
+
+/// `~/`, or `~\` on Windows, replaced with the home dir
+fn expand_home(path: &str) -> PathBuf {
+    let rest = path
+        .strip_prefix("~/")
+        .or_else(|| path.strip_prefix(r"~\").filter(|_| cfg!(windows)));
+    match (rest, dirs::home_dir()) {
+        (Some(rest), Some(home)) => join_slashed(home, rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Exclude pattern with `~/` expanded
+fn expand_pattern(pattern: &str) -> String {
+    if pattern.starts_with('~') {
+        expand_home(pattern).to_string_lossy().into_owned()
+    } else {
+        pattern.to_owned()
+    }
+}
+
+/// `base` followed by each `/` separated part of `rest`, so Windows paths don't end up mixing
+/// separators
+fn join_slashed(mut base: PathBuf, rest: &str) -> PathBuf {
+    base.extend(rest.split('/').filter(|part| !part.is_empty()));
+    base
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_path() {
+        let config = Config {
+            clone_layout: Some("/src/{host}/{owner}/{repo}".to_owned()),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.clone_path("github.com", "gbrls/pplaces"),
+            Some(PathBuf::from("/src/github.com/gbrls/pplaces"))
+        );
+        assert_eq!(Config::default().clone_path("github.com", "a/b"), None);
+    }
+
+    #[test]
+    fn test_workspace() {
+        let work = Workspace {
+            tags: vec!["work".to_owned()],
+            paths: vec!["/work".to_owned()],
+        };
+        assert!(work.contains(Path::new("/work/api"), &[]));
+        assert!(work.contains(Path::new("/src/tool"), &["work".to_owned()]));
+        assert!(!work.contains(Path::new("/src/tool"), &["oss".to_owned()]));
+    }
+
+    #[test]
+    fn test_policy() {
+        let nas = RootPolicy {
+            max_depth: Some(2),
+            sizes: false,
+            ..RootPolicy::default()
+        };
+        let archive = RootPolicy {
+            exclude: vec!["old".to_owned()],
+            ..RootPolicy::default()
+        };
+        let config = Config {
+            policies: HashMap::from([
+                ("/mnt/nas".to_owned(), nas),
+                ("/mnt/nas/archive".to_owned(), archive),
+            ]),
+            ..Config::default()
+        };
+
+        let opts = config
+            .policy(Path::new("/mnt/nas/src"))
+            .apply(&ScanOpts::default());
+        assert_eq!(opts.max_depth, Some(2));
+        assert!(opts.no_sizes && !opts.no_status);
+        let given = ScanOpts {
+            max_depth: Some(5),
+            ..ScanOpts::default()
+        };
+        assert_eq!(
+            config.policy(Path::new("/mnt/nas")).apply(&given).max_depth,
+            Some(5)
+        );
+
+        let opts = config
+            .policy(Path::new("/mnt/nas/archive"))
+            .apply(&ScanOpts::default());
+        assert_eq!(opts.exclude, ["old"]);
+        assert!(!opts.no_sizes);
+        assert!(config.policy(Path::new("/src")).status);
+    }
+
+    #[test]
+    fn test_layout_path() {
+        let config = Config {
+            roots: vec!["/code".to_owned(), "/work".to_owned()],
+            ..Config::default()
+        };
+        assert_eq!(
+            config.layout_path("gitlab.com", "group/repo"),
+            Some(PathBuf::from("/code/gitlab.com/group/repo"))
+        );
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home("~/src/work"), home.join("src").join("work"));
+        assert_eq!(expand_home("/srv/git"), PathBuf::from("/srv/git"));
+        assert_eq!(expand_home("~user/src"), PathBuf::from("~user/src"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Dependency checks that shell out to each ecosystem's own tooling.
+
+use anyhow::{bail, Context, Result};
+use std::{path::Path, process::Command};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Ecosystem {
+    Cargo,
+    Npm,
+}
+
+impl Ecosystem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ecosystem::Cargo => "cargo",
+            Ecosystem::Npm => "npm",
+        }
+    }
+
+    pub fn lockfile(&self) -> &'static str {
+        match self {
+            Ecosystem::Cargo => "Cargo.lock",
+            Ecosystem::Npm => "package-lock.json",
+        }
+    }
+}
+
+/// Ecosystems with a manifest at the root of the repo
+pub fn ecosystems(repo: &Path) -> Vec<Ecosystem> {
+    let mut found = Vec::new();
+    if repo.join("Cargo.toml").is_file() {
+        found.push(Ecosystem::Cargo);
+    }
+    if repo.join("package.json").is_file() {
+        found.push(Ecosystem::Npm);
+    }
+    found
+}
+
+/// Number of direct dependencies that have a newer version available.
+pub fn outdated(repo: &Path, eco: Ecosystem) -> Result<usize> {
+    match eco {
+        Ecosystem::Cargo => {
+            let stdout = run(
+                repo,
+                "cargo",
+                &[
+                    "outdated",
+                    "--workspace",
+                    "--root-deps-only",
+                    "--format",
+                    "json",
+                ],
+                &[0],
+            )?;
+
+            // one json object per workspace member
+            let mut count = 0;
+            for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+                let v = serde_json::from_str::<serde_json::Value>(line)
+                    .context("unexpected cargo outdated output")?;
+                count += v["dependencies"].as_array().map(|d| d.len()).unwrap_or(0);
+            }
+            Ok(count)
+        }
+        Ecosystem::Npm => {
+            // npm exits with 1 when something is outdated
+            let stdout = run(repo, "npm", &["outdated", "--json"], &[0, 1])?;
+            if stdout.trim().is_empty() {
+                return Ok(0);
+            }
+            let v = serde_json::from_str::<serde_json::Value>(&stdout)
+                .context("unexpected npm outdated output")?;
+            Ok(v.as_object().map(|o| o.len()).unwrap_or(0))
+        }
+    }
+}
+
+/// Number of known vulnerabilities in the locked dependencies.
+pub fn audit(repo: &Path, eco: Ecosystem) -> Result<usize> {
+    match eco {
+        Ecosystem::Cargo => {
+            // cargo audit exits with 1 when it finds something
+            let stdout = run(repo, "cargo", &["audit", "--json"], &[0, 1])?;
+            let v = serde_json::from_str::<serde_json::Value>(&stdout)
+                .context("unexpected cargo audit output")?;
+            Ok(v["vulnerabilities"]["count"].as_u64().unwrap_or(0) as usize)
+        }
+        Ecosystem::Npm => {
+            let stdout = run(repo, "npm", &["audit", "--json"], &[0, 1])?;
+            let v = serde_json::from_str::<serde_json::Value>(&stdout)
+                .context("unexpected npm audit output")?;
+            Ok(v["metadata"]["vulnerabilities"]["total"]
+                .as_u64()
+                .unwrap_or(0) as usize)
+        }
+    }
+}
+
+/// Runs `program` inside `repo`, failing unless it exits with one of the `ok` codes.
+fn run(repo: &Path, program: &str, args: &[&str], ok: &[i32]) -> Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .with_context(|| format!("could not run {program}"))?;
+
+    if !output.status.code().is_some_and(|c| ok.contains(&c)) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "{program} {} failed: {}",
+            args[0],
+            stderr.lines().last().unwrap_or("").trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,207 @@
+//! Everything known about a repo for `show --full`: a block per repo starting with its path,
+//! followed by one labelled line per field that has something to say.
+
+use crate::{human_size, ProjectMetadata};
+
+const LABEL_WIDTH: usize = 10;
+
+/// `  label      value`, the lines after the first of `value` lined up under it
+fn field(out: &mut String, label: &str, value: &str) {
+    let indent = format!("\n{}", " ".repeat(LABEL_WIDTH + 3));
+    out.push_str(&format!(
+        "  {label:LABEL_WIDTH$} {}\n",
+        value.replace('\n', &indent)
+    ));
+}
+
+pub fn render(e: &ProjectMetadata) -> String {
+    let mut out = format!("{}\n", e.path);
+
+    let remotes = e
+        .upstream
+        .iter()
+        .filter_map(|u| u.strip_suffix(" (fetch)"))
+        .collect::<Vec<_>>();
+    if remotes.is_empty() {
+        field(&mut out, "remotes", "none");
+    } else {
+        field(&mut out, "remotes", &remotes.join("\n"));
+        let fetched = match e.last_fetch {
+            Some(date) => date.format("%Y-%m-%d %H:%M UTC").to_string(),
+            None => "never".to_owned(),
+        };
+        field(&mut out, "fetched", &fetched);
+    }
+    if let Some(parent) = &e.fork_of {
+        let mut fork = parent.clone();
+        match (e.parent_ahead, e.parent_behind) {
+            (Some(0), Some(0)) => fork.push_str(", even"),
+            (Some(ahead), Some(behind)) => {
+                fork.push_str(&format!(", {ahead} ahead, {behind} behind"))
+            }
+            _ => fork.push_str(", not fetched"),
+        }
+        field(&mut out, "fork of", &fork);
+    }
+
+    let mut branch = match &e.current_branch {
+        Some(branch) => branch.clone(),
+        None if e.bare => "bare".to_owned(),
+        None => "detached HEAD".to_owned(),
+    };
+    if let Some(default) = e.default_branch.as_ref() {
+        if e.current_branch.as_ref() != Some(default) {
+            branch.push_str(&format!(" (default {default})"));
+        }
+    }
+    match (e.ahead, e.behind) {
+        (Some(0), Some(0)) => branch.push_str(", up to date"),
+        (Some(ahead), Some(behind)) => {
+            branch.push_str(&format!(", {ahead} ahead, {behind} behind"))
+        }
+        _ if e.current_branch.is_some() => branch.push_str(", no upstream"),
+        _ => {}
+    }
+    field(&mut out, "branch", &branch);
+
+    let mut changes = if e.dirty {
+        vec![format!(
+            "{} modified, {} untracked",
+            e.modified, e.untracked
+        )]
+    } else {
+        vec!["clean".to_owned()]
+    };
+    if e.stashes > 0 {
+        changes.push(format!("{} stashes", e.stashes));
+    }
+    if e.unpushed > 0 {
+        changes.push(format!("{} unpushed commits", e.unpushed));
+    }
+    field(&mut out, "changes", &changes.join(", "));
+
+    let commit = match e.latest_commit {
+        Some(date) => {
+            let mut commit = date.format("%Y-%m-%d %H:%M %:z").to_string();
+            if let Some(author) = &e.last_commit_author {
+                commit.push_str(&format!(" by {author}"));
+            }
+            if let Some(summary) = &e.last_commit_summary {
+                commit.push_str(&format!("\n{summary}"));
+            }
+            commit
+        }
+        None => "none".to_owned(),
+    };
+    field(&mut out, "commit", &commit);
+    if let Some(tag) = &e.latest_tag {
+        let release = match e.commits_since_tag {
+            0 => format!("{tag}, nothing since"),
+            since => format!("{tag}, {since} commits since"),
+        };
+        field(&mut out, "release", &release);
+    }
+
+    field(&mut out, "size", &human_size(e.size_bytes));
+    if let Some(language) = &e.language {
+        field(&mut out, "language", language);
+    }
+    if !e.tags.is_empty() {
+        field(&mut out, "tags", &e.tags.join(", "));
+    }
+    if !e.top_authors.is_empty() {
+        let authors = e
+            .top_authors
+            .iter()
+            .map(|a| format!("{} ({})", a.name, a.commits))
+            .collect::<Vec<_>>();
+        field(&mut out, "authors", &authors.join(", "));
+    }
+    if let Some(mine) = e.my_commits {
+        field(&mut out, "yours", &format!("{mine} commits"));
+    }
+    if let Some(info) = &e.remote_info {
+        let mut about = info.description.clone().unwrap_or_default();
+        let mut extra = vec![format!("{} stars", info.stars)];
+        if info.fork {
+            extra.push("fork".to_owned());
+        }
+        if info.archived {
+            extra.push("archived".to_owned());
+        }
+        if !about.is_empty() {
+            about.push(' ');
+        }
+        about.push_str(&format!("({})", extra.join(", ")));
+        field(&mut out, "about", &about);
+    }
+    if !e.worktrees.is_empty() {
+        let worktrees = e
+            .worktrees
+            .iter()
+            .map(|w| match &w.branch {
+                Some(branch) => format!("{} ({branch})", w.path),
+                None => w.path.clone(),
+            })
+            .collect::<Vec<_>>();
+        field(&mut out, "worktrees", &worktrees.join("\n"));
+    }
+    if !e.submodules.is_empty() {
+        field(&mut out, "submodules", &e.submodules.join("\n"));
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+
+    #[test]
+    fn test_render() {
+        let e = ProjectMetadata {
+            path: "/src/pplaces".to_owned(),
+            upstream: vec![
+                "git@github.com:gbrls/pplaces.git (fetch)".to_owned(),
+                "git@github.com:gbrls/pplaces.git (push)".to_owned(),
+                "https://github.com/someone/pplaces (fetch)".to_owned(),
+            ],
+            fork_of: Some("https://github.com/someone/pplaces".to_owned()),
+            parent_ahead: Some(1),
+            parent_behind: Some(4),
+            latest_commit: Some(DateTime::parse_from_rfc3339("2024-06-01T12:30:00-03:00").unwrap()),
+            current_branch: Some("fix".to_owned()),
+            default_branch: Some("main".to_owned()),
+            ahead: Some(2),
+            behind: Some(0),
+            dirty: true,
+            modified: 3,
+            untracked: 1,
+            size_bytes: 1536,
+            last_commit_author: Some("Someone".to_owned()),
+            last_commit_summary: Some("Fix the thing".to_owned()),
+            latest_tag: Some("v0.4.0".to_owned()),
+            commits_since_tag: 7,
+            tags: vec!["work".to_owned(), "rust".to_owned()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&e),
+            "/src/pplaces
+  remotes    git@github.com:gbrls/pplaces.git
+             https://github.com/someone/pplaces
+  fetched    never
+  fork of    https://github.com/someone/pplaces, 1 ahead, 4 behind
+  branch     fix (default main), 2 ahead, 0 behind
+  changes    3 modified, 1 untracked
+  commit     2024-06-01 12:30 -03:00 by Someone
+             Fix the thing
+  release    v0.4.0, 7 commits since
+  size       1.5 KiB
+  tags       work, rust
+"
+        );
+    }
+}
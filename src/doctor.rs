@@ -0,0 +1,160 @@
+//! Health checks for `doctor`: setups that make git fail or lose work later on.
+
+use crate::{parallel_map, run_git, status, Cache, ProjectMetadata, RemoteUrl};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    time::Duration,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+pub enum Finding {
+    DetachedHead,
+    NoRemote,
+    /// Remote name and the host that can't be reached
+    DeadHost(String, String),
+    /// Branch without one
+    NoUpstream(String),
+    NoEmail,
+}
+
+impl Finding {
+    pub fn describe(&self) -> String {
+        match self {
+            Finding::DetachedHead => "HEAD is detached".to_owned(),
+            Finding::NoRemote => "no remote configured".to_owned(),
+            Finding::DeadHost(remote, host) => {
+                format!("{remote} points at {host}, which can't be reached")
+            }
+            Finding::NoUpstream(branch) => format!("{branch} has no upstream branch"),
+            Finding::NoEmail => "user.email is not set".to_owned(),
+        }
+    }
+
+    pub fn hint(&self) -> String {
+        match self {
+            Finding::DetachedHead => {
+                "git switch <branch>, or git switch -c <new-branch>".to_owned()
+            }
+            Finding::NoRemote => "pplaces upload, or git remote add origin <url>".to_owned(),
+            Finding::DeadHost(remote, _) => format!("git remote set-url {remote} <url>"),
+            Finding::NoUpstream(branch) => format!("git push -u origin {branch}"),
+            Finding::NoEmail => "git config --global user.email <email>".to_owned(),
+        }
+    }
+}
+
+/// Host and port git connects to for `url`, None for local paths
+fn host_port(url: &str) -> Option<(String, u16)> {
+    let remote = RemoteUrl::parse(url)?;
+    let port = remote.port.or_else(|| remote.default_port())?;
+    Some((remote.host, port))
+}
+
+/// Whether something answers on `host:port`, resolving the name first
+fn reachable(host: &str, port: u16) -> bool {
+    match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs
+            .into_iter()
+            .any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok()),
+        Err(_) => false,
+    }
+}
+
+/// Remote names and fetch urls of the repo
+fn remotes(path: &Path) -> Vec<(String, String)> {
+    let out = run_git(path, &["remote", "-v"]).unwrap_or_default();
+    out.lines()
+        .filter_map(|l| l.strip_suffix(" (fetch)"))
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(name, url)| (name.to_owned(), url.to_owned()))
+        .collect()
+}
+
+/// Checks every repo, each host is only tried once
+pub fn check(data: &Cache, jobs: usize) -> Vec<Result<Vec<Finding>>> {
+    let remotes = parallel_map(data, jobs, |e| remotes(Path::new(&e.path)));
+
+    let mut targets = remotes
+        .iter()
+        .flatten()
+        .filter_map(|(_, url)| host_port(url))
+        .collect::<Vec<_>>();
+    targets.sort();
+    targets.dedup();
+    let up = parallel_map(&targets, jobs, |(host, port)| reachable(host, *port));
+    let up = targets.into_iter().zip(up).collect::<HashMap<_, _>>();
+
+    let items = data.iter().zip(remotes).collect::<Vec<_>>();
+    parallel_map(&items, jobs, |(e, remotes)| check_repo(e, remotes, &up))
+}
+
+fn check_repo(
+    e: &ProjectMetadata,
+    remotes: &[(String, String)],
+    up: &HashMap<(String, u16), bool>,
+) -> Result<Vec<Finding>> {
+    let path = Path::new(&e.path);
+    let repo = status::open(path, e.git_dir.as_deref().map(Path::new))?;
+    let mut found = Vec::new();
+
+    let branch = status::current_branch(&repo);
+    if branch.is_none() && !repo.is_bare() && status::head_commit(&repo).is_some() {
+        found.push(Finding::DetachedHead);
+    }
+
+    if remotes.is_empty() {
+        found.push(Finding::NoRemote);
+    } else if let Some(branch) = branch {
+        let has_upstream = repo
+            .find_branch(&branch, git2::BranchType::Local)
+            .and_then(|b| b.upstream())
+            .is_ok();
+        // an unborn branch has nothing to push yet
+        if !has_upstream && status::head_commit(&repo).is_some() {
+            found.push(Finding::NoUpstream(branch));
+        }
+    }
+
+    for (name, url) in remotes {
+        let dead = host_port(url).is_some_and(|target| up.get(&target) == Some(&false));
+        if dead {
+            let host = RemoteUrl::parse(url).map(|r| r.host).unwrap_or_default();
+            found.push(Finding::DeadHost(name.clone(), host));
+        }
+    }
+
+    if run_git(path, &["config", "user.email"]).is_err() {
+        found.push(Finding::NoEmail);
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_port() {
+        let target = |host: &str, port| Some((host.to_owned(), port));
+        assert_eq!(
+            host_port("https://github.com/gbrls/pplaces"),
+            target("github.com", 443)
+        );
+        assert_eq!(
+            host_port("git@github.com:gbrls/pplaces.git"),
+            target("github.com", 22)
+        );
+        assert_eq!(
+            host_port("ssh://git@git.example.com:2222/a/b.git (fetch)"),
+            target("git.example.com", 2222)
+        );
+        assert_eq!(host_port("/srv/git/b.git"), None);
+        assert_eq!(host_port("file:///srv/git/b.git"), None);
+    }
+}
@@ -0,0 +1,508 @@
+//! Small clients for the forge (GitHub, GitLab, Gitea) REST APIs.
+
+use anyhow::{bail, Context, Result};
+use clap::ArgEnum;
+use hyper::{Body, Client, Method, Request, StatusCode};
+use hyper_tls::HttpsConnector;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{io::Write, process::Command, process::Stdio, sync::OnceLock};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+pub enum Backend {
+    /// Talk to the APIs with our own http client
+    Http,
+    /// Shell out to `gh api` / `glab api`, reusing their login, SSO and proxy setup
+    Cli,
+}
+
+/// Where `upload` creates repos
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Forge {
+    Github,
+    Gitlab,
+    /// Gitea and its forks, Forgejo and Codeberg
+    Gitea,
+}
+
+impl Forge {
+    pub fn default_host(&self) -> &'static str {
+        match self {
+            Forge::Github => "github.com",
+            Forge::Gitlab => "gitlab.com",
+            Forge::Gitea => "codeberg.org",
+        }
+    }
+
+    /// The API call that creates `repo` on `host`, without talking to it. A GitLab group is left
+    /// as `<id of group/path>` in `namespace_id`, [`Forge::create_repo`] looks its id up
+    pub fn create_request(
+        &self,
+        host: &str,
+        repo: &NewRepo<'_>,
+    ) -> Result<(Method, String, serde_json::Value)> {
+        let description = repo.description.unwrap_or("");
+        let call = match self {
+            Forge::Github if host != "github.com" => {
+                bail!("only github.com is supported, {host} is not")
+            }
+            Forge::Github => {
+                let uri = match repo.org {
+                    Some(org) => format!("https://api.github.com/orgs/{org}/repos"),
+                    None => "https://api.github.com/user/repos".to_owned(),
+                };
+                let body = json!({
+                    "name": repo.name,
+                    "private": repo.private,
+                    "description": description,
+                });
+                (Method::POST, uri, body)
+            }
+            Forge::Gitlab => {
+                let mut body = json!({
+                    "name": repo.name,
+                    "path": repo.name,
+                    "visibility": if repo.private { "private" } else { "public" },
+                    "description": description,
+                });
+                if let Some(org) = repo.org {
+                    body["namespace_id"] = format!("<id of {org}>").into();
+                }
+                (
+                    Method::POST,
+                    format!("https://{host}/api/v4/projects"),
+                    body,
+                )
+            }
+            Forge::Gitea => {
+                let uri = match repo.org {
+                    Some(org) => format!("https://{host}/api/v1/orgs/{org}/repos"),
+                    None => format!("https://{host}/api/v1/user/repos"),
+                };
+                let body = json!({
+                    "name": repo.name,
+                    "private": repo.private,
+                    "description": description,
+                });
+                (Method::POST, uri, body)
+            }
+        };
+
+        Ok(call)
+    }
+
+    /// Creates `repo` on `host`, returning its ssh url.
+    pub async fn create_repo(&self, host: &str, repo: &NewRepo<'_>) -> Result<String> {
+        let (method, uri, mut body) = self.create_request(host, repo)?;
+        if let (Forge::Gitlab, Some(org)) = (self, repo.org) {
+            body["namespace_id"] = gitlab_namespace_id(host, org).await?.into();
+        }
+        let auth = match self {
+            Forge::Github => github_auth()?,
+            Forge::Gitlab => gitlab_auth(host)?,
+            // there is no cli for Gitea, it is always reached over http
+            Forge::Gitea if backend() == Backend::Cli => {
+                bail!("--backend cli does not support Gitea, use the http backend")
+            }
+            Forge::Gitea => vec![("Authorization", format!("token {}", gitea_token(host)?))],
+        };
+
+        let (status, body) = request(method, &uri, &auth, Some(body)).await?;
+        if !status.is_success() {
+            bail!("could not create {} on {host}: {status} {body}", repo.name);
+        }
+
+        Ok(serde_json::from_str::<RepoUrl>(&body)?.ssh_url)
+    }
+
+    /// The ssh url of the repo `create_repo` would make when it already exists
+    pub async fn existing_repo(&self, host: &str, repo: &NewRepo<'_>) -> Result<Option<String>> {
+        let (uri, auth) = match self {
+            Forge::Github => {
+                let owner = match repo.org {
+                    Some(org) => org.to_owned(),
+                    None => github_user().await?,
+                };
+                let uri = format!("https://api.github.com/repos/{owner}/{}", repo.name);
+                (uri, github_auth()?)
+            }
+            Forge::Gitlab => {
+                let owner = match repo.org {
+                    Some(org) => org.to_owned(),
+                    None => gitlab_user(host).await?,
+                };
+                // the full path of the project, url encoded
+                let id = format!("{owner}/{}", repo.name).replace('/', "%2F");
+                let uri = format!("https://{host}/api/v4/projects/{id}");
+                (uri, gitlab_auth(host)?)
+            }
+            Forge::Gitea => {
+                let auth = vec![("Authorization", format!("token {}", gitea_token(host)?))];
+                let owner = match repo.org {
+                    Some(org) => org.to_owned(),
+                    None => {
+                        let uri = format!("https://{host}/api/v1/user");
+                        let (status, body) = request(Method::GET, &uri, &auth, None).await?;
+                        if !status.is_success() {
+                            bail!("token rejected ({status})");
+                        }
+                        serde_json::from_str::<User>(&body)?.login
+                    }
+                };
+                let uri = format!("https://{host}/api/v1/repos/{owner}/{}", repo.name);
+                (uri, auth)
+            }
+        };
+
+        let (status, body) = request(Method::GET, &uri, &auth, None).await?;
+        match status {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                Ok(Some(serde_json::from_str::<RepoUrl>(&body)?.ssh_url))
+            }
+            status => bail!("could not look up {} on {host}: {status} {body}", repo.name),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RepoUrl {
+    #[serde(alias = "ssh_url_to_repo")]
+    ssh_url: String,
+}
+
+/// A repo to be created on a forge
+pub struct NewRepo<'a> {
+    pub name: &'a str,
+    /// Organization or group that owns it, the token owner when None
+    pub org: Option<&'a str>,
+    pub private: bool,
+    pub description: Option<&'a str>,
+}
+
+static BACKEND: OnceLock<Backend> = OnceLock::new();
+
+pub fn set_backend(backend: Backend) {
+    let _ = BACKEND.set(backend);
+}
+
+pub fn backend() -> Backend {
+    *BACKEND.get().unwrap_or(&Backend::Http)
+}
+
+const KEYRING_SERVICE: &str = "pplaces";
+
+/// Token for `host`, taken from the environment variable `var`, the `tokens` table of
+/// config.toml or the OS keyring, in that order.
+pub fn token(var: &str, host: &str) -> Result<String> {
+    if let Ok(token) = std::env::var(var) {
+        return Ok(token);
+    }
+    if let Some(token) = crate::config::load()?.tokens.get(host) {
+        return Ok(token.clone());
+    }
+
+    match keyring::Entry::new(KEYRING_SERVICE, host).and_then(|e| e.get_password()) {
+        Ok(token) => Ok(token),
+        Err(_) => bail!(
+            "No token for {host}: set {var}, add it to tokens in config.toml or run `pplaces auth login --host {host}`"
+        ),
+    }
+}
+
+/// Stores the token of `host` in the OS keyring
+pub fn store_token(host: &str, token: &str) -> Result<()> {
+    keyring::Entry::new(KEYRING_SERVICE, host)
+        .and_then(|e| e.set_password(token))
+        .with_context(|| format!("Could not store the token for {host} in the keyring"))
+}
+
+pub fn github_token() -> Result<String> {
+    token("PPLACES_GITHUB_TOKEN", "github.com")
+}
+
+pub fn gitlab_token(host: &str) -> Result<String> {
+    token("PPLACES_GITLAB_TOKEN", host)
+}
+
+pub fn gitea_token(host: &str) -> Result<String> {
+    token("PPLACES_GITEA_TOKEN", host)
+}
+
+fn github_auth() -> Result<Vec<(&'static str, String)>> {
+    match backend() {
+        // gh brings its own credentials
+        Backend::Cli => Ok(Vec::new()),
+        Backend::Http => Ok(vec![(
+            "Authorization",
+            format!("token {}", github_token()?),
+        )]),
+    }
+}
+
+fn gitlab_auth(host: &str) -> Result<Vec<(&'static str, String)>> {
+    match backend() {
+        // glab brings its own credentials
+        Backend::Cli => Ok(Vec::new()),
+        Backend::Http => Ok(vec![("PRIVATE-TOKEN", gitlab_token(host)?)]),
+    }
+}
+
+/// Sends a JSON request and returns the status with the response body.
+pub async fn request(
+    method: Method,
+    uri: &str,
+    headers: &[(&str, String)],
+    body: Option<serde_json::Value>,
+) -> Result<(StatusCode, String)> {
+    // the headers carry the token, they are left out
+    debug!("{method} {uri}");
+    if backend() == Backend::Cli {
+        return cli_request(method, uri, body);
+    }
+
+    let https = HttpsConnector::new();
+    let client = Client::builder().build::<_, hyper::Body>(https);
+
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .header("User-Agent", "pplaces CLI Tool");
+    for (k, v) in headers {
+        req = req.header(*k, v);
+    }
+    let req = match body {
+        Some(body) => req.body(Body::from(body.to_string()))?,
+        None => req.body(Body::empty())?,
+    };
+
+    let res = client.request(req).await?;
+    let status = res.status();
+    let body = hyper::body::to_bytes(res.into_body()).await?;
+
+    Ok((status, String::from_utf8(body.to_vec())?))
+}
+
+/// Sends the request through `gh api` for GitHub and `glab api` for GitLab urls.
+/// The auth headers are dropped, the tools use their own login.
+fn cli_request(
+    method: Method,
+    uri: &str,
+    body: Option<serde_json::Value>,
+) -> Result<(StatusCode, String)> {
+    let mut cmd = if let Some(endpoint) = uri.strip_prefix("https://api.github.com/") {
+        let mut cmd = Command::new("gh");
+        cmd.args(["api", endpoint]);
+        cmd
+    } else {
+        let (host, endpoint) = uri
+            .strip_prefix("https://")
+            .and_then(|rest| rest.split_once("/api/v4/"))
+            .with_context(|| format!("{uri} is neither a GitHub nor a GitLab API url"))?;
+        let mut cmd = Command::new("glab");
+        cmd.args(["api", endpoint, "--hostname", host]);
+        cmd
+    };
+    cmd.args(["--method", method.as_str(), "--include"]);
+    if body.is_some() {
+        cmd.args(["--input", "-"]);
+    }
+
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("could not run {program}, is it installed?"))?;
+    if let Some(body) = body {
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(body.to_string().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    // --include prints the status line and headers before the body
+    let stdout = String::from_utf8(output.stdout)?;
+    let (head, body) = stdout
+        .split_once("\r\n\r\n")
+        .or_else(|| stdout.split_once("\n\n"))
+        .unwrap_or((&stdout, ""));
+    let status = head
+        .lines()
+        .next()
+        .and_then(|line| line.split(' ').nth(1))
+        .and_then(|code| code.parse::<u16>().ok())
+        .and_then(|code| StatusCode::from_u16(code).ok());
+
+    match status {
+        Some(status) => Ok((status, body.to_owned())),
+        None => bail!(
+            "{program} api failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+struct User {
+    #[serde(alias = "username")]
+    login: String,
+}
+
+/// Login of the GitHub token's owner, fails when the token is rejected.
+pub async fn github_user() -> Result<String> {
+    let uri = "https://api.github.com/user";
+    let (status, body) = request(Method::GET, uri, &github_auth()?, None).await?;
+    if !status.is_success() {
+        bail!("token rejected ({status})");
+    }
+
+    Ok(serde_json::from_str::<User>(&body)?.login)
+}
+
+/// Username of the GitLab token's owner on `host`
+pub async fn gitlab_user(host: &str) -> Result<String> {
+    let uri = format!("https://{host}/api/v4/user");
+    let (status, body) = request(Method::GET, &uri, &gitlab_auth(host)?, None).await?;
+    if !status.is_success() {
+        bail!("token rejected ({status})");
+    }
+
+    Ok(serde_json::from_str::<User>(&body)?.login)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GithubRepo {
+    pub name: String,
+    pub private: bool,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub stargazers_count: u64,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default)]
+    pub archived: bool,
+    pub default_branch: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AccountRepo {
+    pub clone_url: String,
+    pub ssh_url: String,
+}
+
+/// Every repo owned by the GitHub user or organization `account`, the private ones too when the
+/// token can see them
+pub async fn github_account_repos(account: &str) -> Result<Vec<AccountRepo>> {
+    #[derive(Deserialize)]
+    struct Account {
+        #[serde(rename = "type")]
+        kind: String,
+    }
+
+    // public repos can be listed without a token
+    let auth = github_auth().unwrap_or_default();
+    let uri = format!("https://api.github.com/users/{account}");
+    let (status, body) = request(Method::GET, &uri, &auth, None).await?;
+    if !status.is_success() {
+        bail!("GitHub answered {status} for {account}: {body}");
+    }
+    let listing = if serde_json::from_str::<Account>(&body)?.kind == "Organization" {
+        format!("https://api.github.com/orgs/{account}/repos?type=all")
+    } else if github_user()
+        .await
+        .is_ok_and(|user| user.eq_ignore_ascii_case(account))
+    {
+        // /users/<name>/repos only lists public repos, even our own
+        "https://api.github.com/user/repos?affiliation=owner".to_owned()
+    } else {
+        format!("https://api.github.com/users/{account}/repos?type=owner")
+    };
+
+    let mut repos = Vec::new();
+    for page in 1.. {
+        let uri = format!("{listing}&per_page=100&page={page}");
+        let (status, body) = request(Method::GET, &uri, &auth, None).await?;
+        if !status.is_success() {
+            bail!("GitHub answered {status} listing the repos of {account}: {body}");
+        }
+        let batch = serde_json::from_str::<Vec<AccountRepo>>(&body)?;
+        let last = batch.len() < 100;
+        repos.extend(batch);
+        if last {
+            break;
+        }
+    }
+
+    Ok(repos)
+}
+
+/// What `enrich` keeps in the cache about a repo from its forge
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteInfo {
+    pub description: Option<String>,
+    pub stars: u64,
+    pub fork: bool,
+    pub archived: bool,
+    pub default_branch: Option<String>,
+}
+
+impl From<GithubRepo> for RemoteInfo {
+    fn from(repo: GithubRepo) -> Self {
+        RemoteInfo {
+            description: repo.description.filter(|d| !d.is_empty()),
+            stars: repo.stargazers_count,
+            fork: repo.fork,
+            archived: repo.archived,
+            default_branch: repo.default_branch,
+        }
+    }
+}
+
+/// Fetches `owner/repo` from the GitHub API
+pub async fn github_repo(owner_repo: &str) -> Result<GithubRepo> {
+    let uri = format!("https://api.github.com/repos/{owner_repo}");
+    let (status, body) = request(Method::GET, &uri, &github_auth()?, None).await?;
+    if !status.is_success() {
+        bail!("GitHub answered {status} for {owner_repo}: {body}");
+    }
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Marks `owner/repo` as archived (read-only) on GitHub
+pub async fn github_archive(owner_repo: &str) -> Result<()> {
+    let uri = format!("https://api.github.com/repos/{owner_repo}");
+    let body = json!({ "archived": true });
+    let (status, body) = request(Method::PATCH, &uri, &github_auth()?, Some(body)).await?;
+    if !status.is_success() {
+        bail!("could not archive {owner_repo}: {status} {body}");
+    }
+
+    Ok(())
+}
+
+/// Id of the group or user namespace `path` on a GitLab instance
+async fn gitlab_namespace_id(host: &str, path: &str) -> Result<u64> {
+    let uri = format!(
+        "https://{host}/api/v4/namespaces/{}",
+        path.replace('/', "%2F")
+    );
+    let (status, body) = request(Method::GET, &uri, &gitlab_auth(host)?, None).await?;
+    if !status.is_success() {
+        bail!("no namespace {path} on {host}: {status} {body}");
+    }
+
+    #[derive(Deserialize)]
+    struct Namespace {
+        id: u64,
+    }
+
+    Ok(serde_json::from_str::<Namespace>(&body)?.id)
+}
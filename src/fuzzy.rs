@@ -0,0 +1,45 @@
+//! Fuzzy matching of queries against repo paths.
+
+/// Scores `text` against `query` when every character of the query appears in order, ignoring
+/// case. Consecutive characters and matches at the start of a path component score higher, as do
+/// matches closer to the end, so the repo name weighs more than its parents.
+pub fn score(query: &str, text: &str) -> Option<i64> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut prev: Option<usize> = None;
+    let mut from = 0;
+
+    for q in query.to_lowercase().chars() {
+        let i = from + text[from..].iter().position(|c| *c == q)?;
+        score += 1;
+        if prev.is_some_and(|p| p + 1 == i) {
+            score += 5;
+        }
+        if i == 0 || matches!(text[i - 1], '/' | '-' | '_' | '.') {
+            score += 3;
+        }
+        prev = Some(i);
+        from = i + 1;
+    }
+
+    // a match in the last component beats the same match in a parent dir
+    let last_slash = text.iter().rposition(|c| *c == '/').unwrap_or(0);
+    if prev.is_some_and(|p| p > last_slash) {
+        score += 10;
+    }
+
+    Some(score - (text.len() as i64 / 10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score() {
+        assert!(score("ppl", "/home/me/src/pplaces").is_some());
+        assert!(score("xyz", "/home/me/src/pplaces").is_none());
+        assert!(score("src", "/src/pplaces") < score("src", "/code/pplaces/src"));
+        assert!(score("pp", "/src/pplaces") > score("pp", "/src/pplaces/apps"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Contribution calendar of the last year for `heatmap`, like the one on GitHub profiles but
+//! counting every local commit, pushed or not.
+//!
+//! A column per week and a row per weekday, each cell shaded by how many commits were made that
+//! day relative to the busiest day.
+
+use crate::theme::Theme;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const WEEKS: i64 = 53;
+
+/// Shade of a day with `count` commits when the busiest one had `max`
+fn level(count: usize, max: usize) -> usize {
+    if count == 0 || max == 0 {
+        return 0;
+    }
+    (count * (SHADES.len() - 1)).div_ceil(max)
+}
+
+/// The calendar up to `today`: a row of month names, then one row per weekday from monday. Plain
+/// text without a `theme`
+pub fn render(dates: &[NaiveDate], today: NaiveDate, theme: Option<&Theme>) -> String {
+    let mut counts = HashMap::<NaiveDate, usize>::new();
+    for d in dates {
+        *counts.entry(*d).or_default() += 1;
+    }
+    let max = counts.values().copied().max().unwrap_or_default();
+
+    let this_monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let start = this_monday - Duration::weeks(WEEKS - 1);
+
+    let mut months = vec![' '; WEEKS as usize];
+    let mut last_month = None;
+    for week in 0..WEEKS {
+        let monday = start + Duration::weeks(week);
+        if last_month == Some(monday.month()) {
+            continue;
+        }
+        last_month = Some(monday.month());
+        // labels that would run into the previous one or past the end are left out
+        let label = monday.format("%b").to_string();
+        let col = week as usize;
+        if col + label.len() <= months.len() && (col == 0 || months[col - 1] == ' ') {
+            for (i, c) in label.chars().enumerate() {
+                months[col + i] = c;
+            }
+        }
+    }
+
+    let mut out = format!("    {}\n", months.iter().collect::<String>().trim_end());
+    for (day, name) in ["Mon", "", "Wed", "", "Fri", "", "Sun"].iter().enumerate() {
+        let mut row = format!("{name:4}");
+        for week in 0..WEEKS {
+            let date = start + Duration::weeks(week) + Duration::days(day as i64);
+            if date > today {
+                break;
+            }
+            let shade = level(counts.get(&date).copied().unwrap_or_default(), max);
+            let cell = SHADES[shade];
+            match theme {
+                Some(t) if shade > 0 => row.push_str(&t.heat.apply(cell).to_string()),
+                Some(t) => row.push_str(&t.heat_empty.apply(cell).to_string()),
+                None => row.push(cell),
+            }
+        }
+        out.push_str(&row);
+        out.push('\n');
+    }
+
+    out
+}
+
+/// `Less ·░▒▓█ More`
+pub fn legend() -> String {
+    format!("Less {} More", SHADES.iter().collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level() {
+        assert_eq!(level(0, 10), 0);
+        assert_eq!(level(1, 10), 1);
+        assert_eq!(level(5, 10), 2);
+        assert_eq!(level(10, 10), 4);
+        assert_eq!(level(3, 0), 0);
+    }
+
+    #[test]
+    fn test_render() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        // a wednesday
+        let today = date("2024-06-05");
+        let dates = [date("2024-06-03"), date("2024-06-03"), date("2024-06-05")];
+
+        let out = render(&dates, today, None);
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 8);
+        assert!(lines[0].trim_start().starts_with("Jun"));
+        assert!(lines[1].starts_with("Mon ") && lines[1].ends_with('█'));
+        assert!(lines[2].ends_with('·'));
+        assert!(lines[3].starts_with("Wed ") && lines[3].ends_with('▒'));
+        // the rest of this week is still to come
+        assert_eq!(lines[4].chars().count(), 4 + 52);
+    }
+}
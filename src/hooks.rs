@@ -0,0 +1,127 @@
+//! User commands run on pplaces events, from the `[hooks]` section of the config.
+//!
+//! A hook runs through the shell, `sh -c` or `cmd /C` on Windows, once the event happened. What
+//! it is about comes in environment variables: `PPLACES_EVENT` always, `PPLACES_REPO_*` for the
+//! events of a repo and a few more per event. A hook that fails is reported, the command that
+//! fired it still succeeds.
+
+use crate::{config::Hooks, ProjectMetadata};
+use std::{path::Path, process::Command};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// `PPLACES_SCAN_ROOTS`, `PPLACES_SCAN_REPOS` and `PPLACES_SCAN_NEW`
+    PostScan,
+    /// The repo and `PPLACES_CLONE_URL`
+    PostClone,
+    /// The repo and `PPLACES_UPLOAD_URL`
+    PostUpload,
+    /// The repo, which is in the trash by then
+    RepoRemoved,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::PostScan => "post-scan",
+            Event::PostClone => "post-clone",
+            Event::PostUpload => "post-upload",
+            Event::RepoRemoved => "repo-removed",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            Event::PostScan => hooks.post_scan.as_deref(),
+            Event::PostClone => hooks.post_clone.as_deref(),
+            Event::PostUpload => hooks.post_upload.as_deref(),
+            Event::RepoRemoved => hooks.repo_removed.as_deref(),
+        }
+    }
+}
+
+/// `PPLACES_REPO_*` of the repo at `path`, from its cache entry when there is one
+pub fn repo_env(path: &Path, entry: Option<&ProjectMetadata>) -> Vec<(&'static str, String)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut env = vec![
+        ("PPLACES_REPO_PATH", path.to_string_lossy().into_owned()),
+        ("PPLACES_REPO_NAME", name.into_owned()),
+    ];
+    if let Some(e) = entry {
+        let remote = e.upstream.iter().find_map(|u| u.strip_suffix(" (fetch)"));
+        env.extend([
+            (
+                "PPLACES_REPO_BRANCH",
+                e.current_branch.clone().unwrap_or_default(),
+            ),
+            ("PPLACES_REPO_REMOTE", remote.unwrap_or_default().to_owned()),
+            (
+                "PPLACES_REPO_DIRTY",
+                if e.dirty { "1" } else { "0" }.to_owned(),
+            ),
+            ("PPLACES_REPO_TAGS", e.tags.join(",")),
+            (
+                "PPLACES_REPO_LANGUAGE",
+                e.language.clone().unwrap_or_default(),
+            ),
+        ]);
+    }
+    env
+}
+
+/// Runs the hook of `event`, if one is set, and waits for it
+pub fn run(hooks: &Hooks, event: Event, env: &[(&str, String)]) {
+    let command = match event.command(hooks) {
+        Some(command) => command,
+        None => return,
+    };
+    debug!("Running the {} hook: {command}", event.name());
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    let status = cmd
+        .env("PPLACES_EVENT", event.name())
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("The {} hook failed, {status}", event.name()),
+        Err(err) => eprintln!("Could not run the {} hook: {err}", event.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        let out = std::env::temp_dir().join(format!("pplaces-hook-{}", std::process::id()));
+        let hooks = Hooks {
+            post_clone: Some(format!(
+                "echo \"$PPLACES_EVENT $PPLACES_REPO_NAME\" > {}",
+                out.display()
+            )),
+            ..Default::default()
+        };
+        run(
+            &hooks,
+            Event::PostClone,
+            &repo_env(Path::new("/src/pplaces"), None),
+        );
+        run(&hooks, Event::PostScan, &[]);
+        assert_eq!(
+            std::fs::read_to_string(&out).unwrap(),
+            "post-clone pplaces\n"
+        );
+        std::fs::remove_file(&out).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+//! Repo lists other tools understand, to move between them and pplaces or between machines.
+//!
+//! - ghq: one `host/owner/repo` per line, what `ghq list` prints and `ghq get` reads
+//! - mr: a `.mrconfig` with a section per repo and the command to clone it
+//! - json: the cache entries themselves
+
+use crate::{run_git, Cache, RemoteUrl};
+use anyhow::{Context, Result};
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+pub enum ListFormat {
+    Ghq,
+    Mr,
+    Json,
+}
+
+pub fn export(data: &Cache, format: ListFormat) -> Result<String> {
+    let mut out = String::new();
+    match format {
+        ListFormat::Ghq => {
+            for url in data.iter().filter_map(|e| origin(&e.upstream)) {
+                if let Some(remote) = RemoteUrl::parse(url) {
+                    out.push_str(&format!("{}/{}\n", remote.host, remote.path));
+                }
+            }
+        }
+        ListFormat::Mr => {
+            for e in data {
+                let url = match origin(&e.upstream) {
+                    Some(url) => url,
+                    // mr can't check it out anywhere else
+                    None => continue,
+                };
+                let dir = Path::new(&e.path)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy();
+                out.push_str(&format!(
+                    "[{}]\ncheckout = git clone '{url}' '{dir}'\n\n",
+                    e.path
+                ));
+            }
+        }
+        ListFormat::Json => out = serde_json::to_string_pretty(data)? + "\n",
+    }
+
+    Ok(out)
+}
+
+/// Paths of the repos listed in `text`. Relative ones are taken from `base`, which is the ghq
+/// root for ghq lists and the directory of the `.mrconfig` for mr.
+pub fn import(text: &str, format: ListFormat, base: &Path) -> Result<Vec<PathBuf>> {
+    let paths = match format {
+        ListFormat::Ghq => text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| base.join(l))
+            .collect(),
+        ListFormat::Mr => text
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix('[')?.strip_suffix(']'))
+            // the DEFAULT section holds settings for every repo
+            .filter(|section| *section != "DEFAULT")
+            .map(|section| base.join(section))
+            .collect(),
+        ListFormat::Json => serde_json::from_str::<Cache>(text)
+            .context("Not a pplaces cache export")?
+            .into_iter()
+            .map(|e| PathBuf::from(e.path))
+            .collect(),
+    };
+
+    Ok(paths)
+}
+
+/// Where ghq clones to: $GHQ_ROOT, then `ghq.root` from the git config, then ~/ghq
+pub fn ghq_root() -> PathBuf {
+    if let Some(root) = env::var_os("GHQ_ROOT") {
+        return PathBuf::from(root);
+    }
+    let configured = run_git(
+        Path::new("."),
+        &["config", "--path", "--get-all", "ghq.root"],
+    );
+    if let Some(root) = configured.ok().as_deref().and_then(|r| r.lines().next()) {
+        return PathBuf::from(root);
+    }
+    dirs::home_dir().unwrap_or_default().join("ghq")
+}
+
+fn origin(upstream: &[String]) -> Option<&str> {
+    upstream.iter().find_map(|u| u.strip_suffix(" (fetch)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProjectMetadata;
+
+    #[test]
+    fn test_round_trip() {
+        let data = vec![
+            ProjectMetadata {
+                path: "/home/me/src/pplaces".to_owned(),
+                upstream: vec!["git@github.com:gbrls/pplaces.git (fetch)".to_owned()],
+                ..Default::default()
+            },
+            ProjectMetadata {
+                path: "/home/me/notes".to_owned(),
+                ..Default::default()
+            },
+        ];
+
+        let ghq = export(&data, ListFormat::Ghq).unwrap();
+        assert_eq!(ghq, "github.com/gbrls/pplaces\n");
+        assert_eq!(
+            import(&ghq, ListFormat::Ghq, Path::new("/home/me/ghq")).unwrap(),
+            [PathBuf::from("/home/me/ghq/github.com/gbrls/pplaces")]
+        );
+
+        let mr = export(&data, ListFormat::Mr).unwrap();
+        assert_eq!(
+            mr,
+            "[/home/me/src/pplaces]\ncheckout = git clone 'git@github.com:gbrls/pplaces.git' 'pplaces'\n\n"
+        );
+        let mr = format!("[DEFAULT]\njobs = 4\n\n{mr}[src/tool]\ncheckout = true\n");
+        assert_eq!(
+            import(&mr, ListFormat::Mr, Path::new("/home/me")).unwrap(),
+            [
+                PathBuf::from("/home/me/src/pplaces"),
+                PathBuf::from("/home/me/src/tool")
+            ]
+        );
+
+        let json = export(&data, ListFormat::Json).unwrap();
+        assert_eq!(
+            import(&json, ListFormat::Json, Path::new("/"))
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
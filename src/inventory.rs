@@ -0,0 +1,246 @@
+//! Machine-readable asset inventory of the cached repos.
+//!
+//! The JSON schema is documented in the readme. It is versioned with `schema_version`: fields are
+//! only ever added within a version, renaming or removing one bumps it.
+
+use crate::RemoteUrl;
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::{collections::HashMap, fs, path::Path};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Inventory {
+    pub schema_version: u32,
+    /// RFC 3339
+    pub generated_at: String,
+    pub hostname: String,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Serialize)]
+pub struct Asset {
+    pub path: String,
+    pub remotes: Vec<Remote>,
+    /// Owner (user or organization) of the origin remote
+    pub owner: Option<String>,
+    /// SPDX identifier, "NOASSERTION" when there is a license we can't identify
+    pub license: Option<String>,
+    /// Date of the latest commit, RFC 3339
+    pub last_activity: Option<String>,
+    /// Work tree and git dir
+    pub size_bytes: u64,
+    /// Most common language among the tracked files
+    pub language: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+}
+
+/// Builds the record of the repo at `path`
+pub fn asset(path: &Path, latest_commit: Option<DateTime<FixedOffset>>) -> Asset {
+    let remotes = remotes(path);
+    let origin = remotes
+        .iter()
+        .find(|r| r.name == "origin")
+        .or_else(|| remotes.first());
+    let owner = origin
+        .and_then(|r| RemoteUrl::parse(&r.url))
+        .map(|r| r.owner().to_owned());
+
+    Asset {
+        path: path.to_str().unwrap().to_owned(),
+        owner,
+        license: license(path),
+        last_activity: latest_commit.map(|d| d.to_rfc3339()),
+        size_bytes: dir_size(path),
+        language: dominant_language(path),
+        remotes,
+    }
+}
+
+fn remotes(path: &Path) -> Vec<Remote> {
+    let out = crate::run_git(path, &["remote", "-v"]).unwrap_or_default();
+
+    let mut remotes: Vec<Remote> = Vec::new();
+    for line in out.lines() {
+        // origin\tgit@github.com:user/repo.git (fetch)
+        let (name, rest) = match line.split_once('\t') {
+            Some(parts) => parts,
+            None => continue,
+        };
+        let url = rest.split(' ').next().unwrap_or(rest);
+        if !remotes.iter().any(|r| r.name == name && r.url == url) {
+            remotes.push(Remote {
+                name: name.to_owned(),
+                url: url.to_owned(),
+            });
+        }
+    }
+
+    remotes
+}
+
+/// SPDX id from the package manifests, falling back to recognizing the license file.
+pub fn license(path: &Path) -> Option<String> {
+    let cargo = fs::read_to_string(path.join("Cargo.toml"))
+        .ok()
+        .and_then(|s| s.parse::<toml::Value>().ok())
+        .and_then(|v| {
+            v.get("package")?
+                .get("license")?
+                .as_str()
+                .map(|s| s.to_owned())
+        });
+    let npm = || {
+        fs::read_to_string(path.join("package.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            .and_then(|v| v.get("license")?.as_str().map(|s| s.to_owned()))
+    };
+    if let Some(id) = cargo.or_else(npm) {
+        return Some(id);
+    }
+
+    let file = fs::read_dir(path).ok()?.flatten().find(|e| {
+        let name = e.file_name().to_string_lossy().to_uppercase();
+        name.starts_with("LICENSE") || name.starts_with("LICENCE") || name.starts_with("COPYING")
+    })?;
+    let text = fs::read_to_string(file.path()).unwrap_or_default();
+
+    Some(identify_license(&text).unwrap_or("NOASSERTION").to_owned())
+}
+
+fn identify_license(text: &str) -> Option<&'static str> {
+    let has = |s: &str| text.contains(s);
+
+    let id = if has("GNU AFFERO GENERAL PUBLIC LICENSE") {
+        "AGPL-3.0"
+    } else if has("GNU LESSER GENERAL PUBLIC LICENSE") {
+        if has("Version 2.1") {
+            "LGPL-2.1"
+        } else {
+            "LGPL-3.0"
+        }
+    } else if has("GNU GENERAL PUBLIC LICENSE") {
+        if has("Version 2") {
+            "GPL-2.0"
+        } else {
+            "GPL-3.0"
+        }
+    } else if has("Apache License") && has("Version 2.0") {
+        "Apache-2.0"
+    } else if has("Mozilla Public License Version 2.0") {
+        "MPL-2.0"
+    } else if has("Permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has("Redistribution and use in source and binary forms") {
+        if has("Neither the name") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("Permission to use, copy, modify, and/or distribute") {
+        "ISC"
+    } else if has("This is free and unencumbered software") {
+        "Unlicense"
+    } else {
+        return None;
+    };
+
+    Some(id)
+}
+
+/// Total size of the files under `path`, symlinks are not followed.
+pub fn dir_size(path: &Path) -> u64 {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+
+    entries
+        .flatten()
+        .map(|e| match e.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&e.path()),
+            Ok(t) if t.is_file() => e.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Language with the most tracked files, going by extension.
+pub fn dominant_language(path: &Path) -> Option<String> {
+    let files = crate::run_git(path, &["ls-files"]).ok()?;
+    language_of_files(files.lines())
+}
+
+/// Most common language among `files`, going by extension.
+pub fn language_of_files<'a>(files: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut counts = HashMap::new();
+    for file in files {
+        let ext = match Path::new(file).extension() {
+            Some(ext) => ext.to_string_lossy().to_lowercase(),
+            None => continue,
+        };
+        if let Some(lang) = language_of(&ext) {
+            *counts.entry(lang).or_insert(0) += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .max_by_key(|(lang, n)| (*n, *lang))
+        .map(|(lang, _)| lang.to_owned())
+}
+
+fn language_of(ext: &str) -> Option<&'static str> {
+    let lang = match ext {
+        "rs" => "Rust",
+        "go" => "Go",
+        "py" => "Python",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "C++",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "swift" => "Swift",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "cs" => "C#",
+        "hs" => "Haskell",
+        "ml" | "mli" => "OCaml",
+        "ex" | "exs" => "Elixir",
+        "erl" => "Erlang",
+        "clj" | "cljs" => "Clojure",
+        "scala" => "Scala",
+        "lua" => "Lua",
+        "zig" => "Zig",
+        "nim" => "Nim",
+        "dart" => "Dart",
+        "sh" | "bash" | "zsh" => "Shell",
+        "asm" | "s" => "Assembly",
+        "tex" => "TeX",
+        _ => return None,
+    };
+
+    Some(lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_license() {
+        let mit = "MIT License\n\nPermission is hereby granted, free of charge, to any person";
+        assert_eq!(identify_license(mit), Some("MIT"));
+        let gpl2 = "GNU GENERAL PUBLIC LICENSE\n Version 2, June 1991";
+        assert_eq!(identify_license(gpl2), Some("GPL-2.0"));
+        assert_eq!(identify_license("All rights reserved"), None);
+    }
+}
@@ -0,0 +1,92 @@
+//! Progress journal for long bulk operations, so an interrupted run can be resumed.
+//!
+//! The journal is an append-only file of `key\tstate` lines where the last line for a key wins.
+//! It is removed once the operation finishes cleanly. The one of the trash never finishes, it
+//! records what `undo` can put back.
+
+use anyhow::{bail, Context, Result};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+/// State of a key whose work is complete, a resumed run leaves it out
+pub const DONE: &str = "done";
+
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    states: HashMap<String, String>,
+}
+
+impl Journal {
+    /// Opens the journal of the operation `name`. Without `resume` a leftover journal from an
+    /// interrupted run is an error, so progress isn't thrown away by accident.
+    pub fn open(name: &str, resume: bool) -> Result<Journal> {
+        let dir = crate::data_dir()
+            .context("No data directory")?
+            .join("journal");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{name}.log"));
+
+        let mut states = HashMap::new();
+        if path.exists() {
+            if !resume {
+                bail!(
+                    "A previous {name} was interrupted, pass --resume to continue it or delete {path:?} to start over"
+                );
+            }
+            for line in fs::read_to_string(&path)?.lines() {
+                if let Some((key, state)) = line.split_once('\t') {
+                    states.insert(key.to_owned(), state.to_owned());
+                }
+            }
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Journal { path, file, states })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.states.get(key).map(|s| s.as_str())
+    }
+
+    /// `items` whose key isn't [`DONE`] yet, saying how many are left out when some are
+    pub fn pending<T: Clone>(&self, items: &[T], key: impl Fn(&T) -> &str) -> Vec<T> {
+        let pending = items
+            .iter()
+            .filter(|i| self.get(key(i)) != Some(DONE))
+            .cloned()
+            .collect::<Vec<_>>();
+        if pending.len() < items.len() {
+            println!(
+                "Skipping {} done before the interruption",
+                items.len() - pending.len()
+            );
+        }
+        pending
+    }
+
+    /// Every key with its latest state
+    pub fn states(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.states.iter().map(|(k, s)| (k.as_str(), s.as_str()))
+    }
+
+    /// Records `state` for `key`, it is on disk once this returns.
+    pub fn record(&mut self, key: &str, state: &str) -> Result<()> {
+        writeln!(self.file, "{key}\t{state}")?;
+        self.file.sync_data()?;
+        self.states.insert(key.to_owned(), state.to_owned());
+        Ok(())
+    }
+
+    /// The operation completed, nothing is left to resume.
+    pub fn finish(self) -> Result<()> {
+        drop(self.file);
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
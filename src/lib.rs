@@ -0,0 +1,396 @@
+//! Repo discovery and the cache behind the `pplaces` CLI, for tools that want to know about the
+//! local git repos without running it.
+//!
+//! [`build_cache`] walks directories looking for repos and reads a [`ProjectMetadata`] for each
+//! one. [`get_cache_from_disk`], [`save_cache_to_disk`] and [`merge_cache_to_disk`] share the
+//! cache the CLI keeps in [`data_dir`], and [`forge`] talks to GitHub, GitLab and Gitea.
+//!
+//! ```no_run
+//! use pplaces_core::{build_cache, get_cache_from_disk, ScanOpts};
+//! use std::path::PathBuf;
+//!
+//! let cache = get_cache_from_disk().unwrap_or_default();
+//! let roots = [PathBuf::from("/home/me/src")];
+//! let (cache, _skipped) = build_cache(cache, &roots, &ScanOpts::default())?;
+//! for repo in cache.iter().filter(|r| r.dirty) {
+//!     println!("{} has uncommitted changes", repo.path);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use rayon::prelude::*;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use std::env;
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::debug;
+
+pub mod authors;
+pub mod config;
+pub mod forge;
+pub mod inventory;
+pub mod manifest;
+pub mod remote;
+pub mod roots;
+pub mod scan;
+pub mod status;
+pub mod store;
+
+pub use remote::{RemoteUrl, Rewrites};
+pub use scan::{
+    build_cache, build_cache_with_progress, fetch_metadata, fetch_scanned, is_bare_repo,
+    is_excluded, ScanOpts, ScanProgress, ScanSkips,
+};
+
+/// Every known repo, the newest commit first after a scan
+pub type Cache = Vec<ProjectMetadata>;
+
+/// What the cache knows about a repo, read from it by [`fetch_metadata`]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProjectMetadata {
+    pub path: String,
+    pub upstream: Vec<String>,
+    /// Committer date in the committer's timezone
+    #[serde(default, deserialize_with = "de_commit_date")]
+    pub latest_commit: Option<DateTime<FixedOffset>>,
+    /// First line of the message of the commit HEAD points at
+    #[serde(default)]
+    pub last_commit_summary: Option<String>,
+    /// Author name of the commit HEAD points at
+    #[serde(default)]
+    pub last_commit_author: Option<String>,
+    #[serde(default)]
+    pub has_docker: bool,
+    /// Linked work trees, they don't get entries of their own
+    #[serde(default)]
+    pub worktrees: Vec<status::Worktree>,
+    /// Paths of the submodules from `.gitmodules`, they only get entries of their own with
+    /// `scan --include-submodules`
+    #[serde(default)]
+    pub submodules: Vec<String>,
+    /// Nested projects when the repo is a monorepo
+    #[serde(default)]
+    pub projects: Vec<manifest::SubProject>,
+    /// Set when the git dir lives outside of `path`
+    #[serde(default)]
+    pub git_dir: Option<String>,
+    /// Has uncommitted changes or untracked files
+    #[serde(default)]
+    pub dirty: bool,
+    /// Tracked files with uncommitted changes, staged or not
+    #[serde(default)]
+    pub modified: usize,
+    /// Files that are neither tracked nor ignored
+    #[serde(default)]
+    pub untracked: usize,
+    /// Commits the checked out branch has that its upstream doesn't, None without an upstream
+    #[serde(default)]
+    pub ahead: Option<usize>,
+    /// Commits the upstream has that the checked out branch doesn't
+    #[serde(default)]
+    pub behind: Option<usize>,
+    /// Fetch url of the `upstream` remote when there is an `origin` too, the usual setup of a
+    /// fork
+    #[serde(default)]
+    pub fork_of: Option<String>,
+    /// Commits HEAD has that the branch of the same name on `upstream` doesn't, None when it
+    /// isn't a fork or that branch wasn't fetched
+    #[serde(default)]
+    pub parent_ahead: Option<usize>,
+    /// Commits the branch on `upstream` has that HEAD doesn't
+    #[serde(default)]
+    pub parent_behind: Option<usize>,
+    /// Last time the repo fetched from a remote, None when it never did
+    #[serde(default)]
+    pub last_fetch: Option<DateTime<Utc>>,
+    /// Closest tag HEAD descends from, like `git describe --tags --abbrev=0`. A tag made without
+    /// new commits shows up once the repo fetches or is scanned with `--force`
+    #[serde(default)]
+    pub latest_tag: Option<String>,
+    /// Commits HEAD is past `latest_tag`
+    #[serde(default)]
+    pub commits_since_tag: usize,
+    /// Commits on local branches that were never pushed to any remote
+    #[serde(default)]
+    pub unpushed: usize,
+    /// None when HEAD is detached
+    #[serde(default)]
+    pub current_branch: Option<String>,
+    /// What origin/HEAD points at
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    #[serde(default)]
+    pub stashes: usize,
+    /// Size of the work tree including the git dir
+    #[serde(default)]
+    pub size_bytes: u64,
+    /// Most common language among the tracked files
+    #[serde(default)]
+    pub language: Option<String>,
+    /// A repo without a work tree, its path is the git dir
+    #[serde(default)]
+    pub bare: bool,
+    /// Set with `pplaces tag`, kept when the repo is read again
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Commit HEAD pointed at, rescans only update the status and size of the repo while it, the
+    /// branch and the last fetch stay the same
+    #[serde(default)]
+    pub head_commit: Option<String>,
+    /// Authors with the most commits on local branches, most first. Only counted by
+    /// `scan --authors`
+    #[serde(default)]
+    pub top_authors: Vec<authors::AuthorCommits>,
+    /// Commits made with the repo's `user.email`, None until a `scan --authors` counted them
+    #[serde(default)]
+    pub my_commits: Option<usize>,
+    /// Description, stars and the like from GitHub, set with `pplaces enrich`
+    #[serde(default)]
+    pub remote_info: Option<forge::RemoteInfo>,
+    //latest_modification:
+}
+
+/// Reads commit dates with an offset. Older caches stored them without one, in the committer's
+/// timezone, those are read as local time which is the best guess left.
+fn de_commit_date<'de, D: Deserializer<'de>>(
+    de: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    let date = match Option::<String>::deserialize(de)? {
+        Some(date) => date,
+        None => return Ok(None),
+    };
+    if let Ok(date) = DateTime::parse_from_rfc3339(&date) {
+        return Ok(Some(date));
+    }
+
+    let naive = date.parse::<NaiveDateTime>().map_err(D::Error::custom)?;
+    let local = Local.from_local_datetime(&naive).earliest();
+    Ok(Some(match local {
+        Some(local) => local.into(),
+        None => Utc.from_utc_datetime(&naive).into(),
+    }))
+}
+
+/// Reads the repo at `path` again and writes its entry through to the store, the tags it had are
+/// kept. This is O(n)
+pub fn update_repo_data(path: &Path, cache: &mut Cache) -> Result<()> {
+    update_repo_data_with(path, cache, &ScanOpts::default())
+}
+
+/// [`update_repo_data`] reading only what `opts` asks for, see [`fetch_scanned`]
+pub fn update_repo_data_with(path: &Path, cache: &mut Cache, opts: &ScanOpts) -> Result<()> {
+    // Registered repos keep their git dir between updates.
+    let git_dir = cache
+        .iter()
+        .find(|e| Path::new(&e.path) == path)
+        .and_then(|e| e.git_dir.clone());
+    // Repos that can't be opened anymore keep their old entry.
+    if let Ok(mut data) = fetch_scanned(path, git_dir.as_deref().map(Path::new), opts) {
+        if let Some(old) = cache.iter().find(|e| e.path == data.path) {
+            keep_user_fields(&mut data, old);
+        }
+        store::open()?.upsert(std::slice::from_ref(&data))?;
+        upsert_repo(data, cache);
+    }
+
+    Ok(())
+}
+
+/// Replaces the entry with the same path, or adds a new one
+pub fn upsert_repo(mut data: ProjectMetadata, cache: &mut Cache) {
+    // We assume that there won't be repetition, so a Vec is just fine.
+    let idx = cache.iter().enumerate().find(|(_, e)| e.path == data.path);
+
+    if let Some((i, _)) = idx {
+        let old = cache.swap_remove(i);
+        keep_user_fields(&mut data, &old);
+    }
+
+    cache.push(data);
+}
+
+/// Carries over what was set by hand or fetched from the forge, a fresh read of the repo doesn't
+/// know about it. The author counts stay until the next `scan --authors` too
+pub fn keep_user_fields(data: &mut ProjectMetadata, old: &ProjectMetadata) {
+    data.tags = old.tags.clone();
+    data.remote_info = old.remote_info.clone();
+    if data.my_commits.is_none() {
+        data.top_authors = old.top_authors.clone();
+        data.my_commits = old.my_commits;
+    }
+}
+
+/// Collects the metadata of the entries matching `pred` again, in parallel.
+pub fn refresh_metadata(cache: &mut Cache, pred: impl Fn(&ProjectMetadata) -> bool) {
+    let stale = cache
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| pred(e))
+        .map(|(i, e)| (i, e.path.clone(), e.git_dir.clone()))
+        .collect::<Vec<_>>();
+
+    let fresh = parallel_map(&stale, default_jobs(), |(_, path, git_dir)| {
+        fetch_metadata(Path::new(path), git_dir.as_deref().map(Path::new)).ok()
+    });
+
+    for ((i, _, _), data) in stale.iter().zip(fresh) {
+        if let Some(mut data) = data {
+            keep_user_fields(&mut data, &cache[*i]);
+            cache[*i] = data;
+        }
+    }
+}
+
+/// Adds a repo whose git dir is not `.git` inside its work tree, like `git init --separate-git-dir`
+/// or a bare dotfiles repo checked out at $HOME.
+pub fn register_repo(work_tree: &Path, git_dir: &Path, cache: &mut Cache) -> Result<()> {
+    let work_tree =
+        canonical_path(work_tree).with_context(|| format!("{work_tree:?} does not exist"))?;
+    let git_dir = canonical_path(git_dir).with_context(|| format!("{git_dir:?} does not exist"))?;
+
+    let data = fetch_metadata(&work_tree, Some(&git_dir))
+        .with_context(|| format!("{git_dir:?} is not a git directory"))?;
+    upsert_repo(data, cache);
+
+    Ok(())
+}
+
+/// `path` made absolute with symlinks resolved, the form paths take in the cache. On Windows
+/// this is `C:\src\repo` rather than the `\\?\C:\src\repo` of [`Path::canonicalize`], which
+/// wouldn't match the paths git, the shell or the current directory give.
+pub fn canonical_path(path: &Path) -> std::io::Result<PathBuf> {
+    dunce::canonicalize(path)
+}
+
+/// Committer date with the committer's offset, like `git log --format=%cI` shows it
+pub fn commit_date(commit: &git2::Commit) -> DateTime<FixedOffset> {
+    let time = commit.time();
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    offset.timestamp_opt(time.seconds(), 0).unwrap()
+}
+
+/// Where `config.toml` and the registered roots live
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(config_dir) = dirs::config_dir() {
+        let config_dir = config_dir.join("pplaces");
+        Some(config_dir)
+    } else {
+        None
+    }
+}
+
+/// Where derived state is kept: the cache, the caches of other machines and journals.
+/// `PPLACES_DATA_DIR` overrides the platform's data dir, `$XDG_DATA_HOME/pplaces` on Linux.
+pub fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("PPLACES_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::data_dir().map(|d| d.join("pplaces"))
+}
+
+/// Replaces the cache in the data dir with `cache`
+pub fn save_cache_to_disk(cache: &Cache) -> Result<()> {
+    // We don't fail without a data dir because it should work even without a disk cache.
+    if data_dir().is_some() {
+        store::open()?
+            .save(cache)
+            .context("Could not save the cache")?;
+    }
+    Ok(())
+}
+
+/// Saves `cache`, which was made from `base` read earlier, keeping what other pplaces wrote to
+/// the cache since. `cache` ends up as what was saved
+pub fn merge_cache_to_disk(base: &Cache, cache: &mut Cache) -> Result<()> {
+    if data_dir().is_some() {
+        store::open()?
+            .save_merged(base, cache)
+            .context("Could not save the cache")?;
+    }
+    Ok(())
+}
+
+/// The cache as the last scan or update left it
+pub fn get_cache_from_disk() -> Result<Cache> {
+    store::open()?.load()
+}
+
+/// Runs `f` over `items` using up to `jobs` threads, keeping the results in order.
+pub fn parallel_map<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .unwrap();
+
+    pool.install(|| items.par_iter().map(f).collect())
+}
+
+/// Threads used when no `--jobs` is given, one per core
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+}
+
+/// Runs git inside `dir`, returning its stdout or failing with its stderr.
+pub fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    debug!("git -C {} {}", dir.display(), args.join(" "));
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Failed to run git")?;
+
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commit_date_compat() {
+        let old = r#"{"path": "/a", "upstream": [], "latest_commit": "2022-01-01T12:00:00"}"#;
+        let old = serde_json::from_str::<ProjectMetadata>(old).unwrap();
+        let naive = "2022-01-01T12:00:00".parse::<NaiveDateTime>().unwrap();
+        assert_eq!(
+            old.latest_commit
+                .unwrap()
+                .with_timezone(&Local)
+                .naive_local(),
+            naive
+        );
+
+        let new = r#"{"path": "/a", "upstream": [], "latest_commit": "2022-01-01T12:00:00-03:00"}"#;
+        let new = serde_json::from_str::<ProjectMetadata>(new).unwrap();
+        assert_eq!(
+            new.latest_commit.unwrap().to_rfc3339(),
+            "2022-01-01T12:00:00-03:00"
+        );
+
+        let none = r#"{"path": "/a", "upstream": [], "latest_commit": null}"#;
+        assert!(serde_json::from_str::<ProjectMetadata>(none)
+            .unwrap()
+            .latest_commit
+            .is_none());
+    }
+}
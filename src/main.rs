@@ -1,409 +1,4187 @@
 #![feature(type_alias_impl_trait, exit_status_error)]
 
-use anyhow::{Context, Result};
-use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
-use clap::Parser;
-use hyper::Body;
-use hyper::{Client, Method, Request};
-use hyper_tls::HttpsConnector;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, FixedOffset, Local, NaiveDate, TimeZone, Utc};
+use clap::{ArgEnum, Args, Parser};
+use indicatif::{ProgressBar, ProgressStyle};
+use pplaces_core::{
+    build_cache, build_cache_with_progress, canonical_path, config, config_dir, data_dir,
+    default_jobs, fetch_metadata, forge, get_cache_from_disk, inventory, is_bare_repo, is_excluded,
+    keep_user_fields, manifest, merge_cache_to_disk, parallel_map, refresh_metadata, register_repo,
+    roots, run_git, save_cache_to_disk, status, store, update_repo_data, update_repo_data_with,
+    Cache, ProjectMetadata, RemoteUrl, Rewrites, ScanOpts, ScanProgress, ScanSkips,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
 use std::env;
-use std::io::{stdout, Read, Write};
+use std::io::{stdout, IsTerminal, Read, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, OnceLock,
+};
 use std::{
+    collections::{BTreeMap, HashSet},
     fs,
     path::{Path, PathBuf},
     process::Command,
 };
+use tracing::{debug, info, Level};
+use tracing_subscriber::{filter::Targets, prelude::*};
 
-type Cache = Vec<ProjectMetadata>;
+mod backup;
+mod completions;
+mod deps;
+mod details;
+mod doctor;
+mod fuzzy;
+mod heatmap;
+mod hooks;
+mod interop;
+mod journal;
+mod stats;
+mod sync;
+mod table;
+mod theme;
+mod trash;
+mod tui;
+mod watch;
 
 #[derive(Serialize, Deserialize, Debug, Parser)]
 enum CmdType {
-    /// Recursively look for git repositories in given path
-    Scan { path: String },
+    /// Recursively look for git repositories in the given paths or every root
+    Scan {
+        /// Remembered as roots, so a later bare `scan` looks at them again
+        paths: Vec<String>,
+
+        /// Also drop cached repos that are gone from disk, like `prune`
+        #[clap(long)]
+        prune: bool,
+
+        #[clap(flatten)]
+        opts: ScanOpts,
+    },
+    /// Attach tags to repos, for `show --tag` and workspaces
+    Tag {
+        #[clap(subcommand)]
+        cmd: TagCmd,
+    },
+    /// Manage the directories a bare `scan` looks at
+    Root {
+        #[clap(subcommand)]
+        cmd: RootCmd,
+    },
+    /// Check the cache against the repos on disk, build it again or share it between machines
+    Cache {
+        #[clap(subcommand)]
+        cmd: CacheCmd,
+    },
+    /// Keep the cache current by watching the given path or every root for changes
+    Watch { path: Option<String> },
+    /// Drop cached repos that were deleted or moved away
+    Prune,
+    /// Move a repo to the trash and drop it from the cache, unless it has work that isn't on any
+    /// remote. Only the git dir of repos registered with `add` is removed, their files stay
+    Remove {
+        path: String,
+
+        /// Remove it even with uncommitted changes, stashes or unpushed commits
+        #[clap(long)]
+        force: bool,
+    },
+    /// Put back the last repo `remove` moved to the trash, or the last one removed from `path`
+    Undo {
+        path: Option<String>,
+
+        /// List what is in the trash instead
+        #[clap(long)]
+        list: bool,
+    },
+    /// Move a repo to another directory and rewrite its cache entry, tags included
+    Mv {
+        old_path: String,
+
+        /// Destination, or an existing directory to move the repo into
+        new_path: String,
+    },
+    /// Register a repo whose git dir lives outside its work tree
+    Add {
+        #[clap(long)]
+        git_dir: String,
+
+        #[clap(long)]
+        work_tree: String,
+    },
     /// Wrapper around git clone to check if the repo is already cloned
-    Clone { args: Vec<String> },
+    Clone {
+        /// Clone into <root>/<host>/<owner>/<repo>, or clone_layout from the config, and print
+        /// the path
+        #[clap(long)]
+        layout: bool,
+
+        /// Clone every url of this file that isn't cloned yet, one per line, # starts a comment
+        #[clap(long, conflicts_with = "args")]
+        from_file: Option<String>,
+
+        /// Clone even when a repo of the same name from another owner is already there
+        #[clap(short, long)]
+        yes: bool,
+
+        /// Continue an interrupted --from-file instead of starting over
+        #[clap(long, requires = "from-file")]
+        resume: bool,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+
+        args: Vec<String>,
+    },
+    /// Clone every repo of a GitHub user or organization that isn't cloned yet into the layout
+    CloneAll {
+        /// Like github.com/gbrls
+        account: String,
+
+        /// Clone over https instead of ssh
+        #[clap(long)]
+        https: bool,
+
+        /// Continue an interrupted clone-all instead of starting over
+        #[clap(long)]
+        resume: bool,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
     /// Show all git repos with some metadata
-    Show,
-    /// Upload repo to github
-    Upload,
+    Show {
+        /// Only show repos with a Dockerfile, compose file or kubernetes manifests
+        #[clap(long)]
+        has_docker: bool,
+
+        /// Expand monorepos into their sub-projects
+        #[clap(long)]
+        projects: bool,
+
+        /// Separate paths with NUL instead of newlines, for piping into other commands
+        #[clap(short = '0', long)]
+        null: bool,
+
+        /// Collect fresh metadata for the shown repos before printing them
+        #[clap(long)]
+        refresh: bool,
+
+        /// Show the repos of another machine (see sync-cache) and whether they are cloned here
+        #[clap(long)]
+        machine: Option<String>,
+
+        /// Only show repos with uncommitted changes or untracked files
+        #[clap(long)]
+        dirty: bool,
+
+        /// Only show repos with local commits that aren't on any remote
+        #[clap(long)]
+        unpushed: bool,
+
+        /// Only show repos with stashes
+        #[clap(long)]
+        with_stashes: bool,
+
+        /// Only show forks: repos with both an origin and an upstream remote
+        #[clap(long)]
+        forks: bool,
+
+        /// Only show forks missing commits of their upstream remote, as of the last fetch
+        #[clap(long)]
+        forks_behind_upstream: bool,
+
+        /// Only show repos with commits since their latest tag, the ones that need a release
+        #[clap(long)]
+        unreleased: bool,
+
+        /// Hide repos without a commit of yours, going by the user.email of each repo. The
+        /// commits are counted by `scan --authors`, repos it didn't count are kept
+        #[clap(long)]
+        mine_only: bool,
+
+        /// Only show repos with a remote that didn't fetch since then, like `30d` or a date. Repos
+        /// that never fetched are shown too
+        #[clap(long)]
+        not_fetched_since: Option<String>,
+
+        /// Only show repos mostly written in this language, e.g. rust
+        #[clap(long)]
+        lang: Option<String>,
+
+        /// Only show repos with a remote on this host, e.g. github.com
+        #[clap(long)]
+        host: Option<String>,
+
+        /// Only show repos with a remote owned by this user or group
+        #[clap(long)]
+        owner: Option<String>,
+
+        /// Only show repos without any remote
+        #[clap(long, conflicts_with_all = &["host", "owner"])]
+        no_remote: bool,
+
+        /// Only show repos with this tag
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Only show the repos of a workspace defined in config.toml
+        #[clap(long)]
+        workspace: Option<String>,
+
+        /// Order of the repos, by default the newest commit comes first
+        #[clap(arg_enum, long)]
+        sort: Option<ShowSort>,
+
+        /// List the repos under a header per group, with the number of repos in it
+        #[clap(arg_enum, long)]
+        group_by: Option<ShowGroup>,
+
+        /// Print a table with the last commit age, branch, uncommitted changes and remote
+        #[clap(long)]
+        table: bool,
+
+        /// No colors in the table, they are also left out when stdout isn't a terminal
+        #[clap(long)]
+        plain: bool,
+
+        /// Only show the repo at this path, which may be relative, with everything known about it
+        /// like --full
+        #[clap(long)]
+        repo: Option<String>,
+
+        /// Print structured records instead of paths
+        #[clap(arg_enum, long)]
+        format: Option<ShowFormat>,
+    },
+    /// Create a repo under the first root from a template, with an initial commit
+    New {
+        name: String,
+
+        #[clap(arg_enum, long, default_value = "empty")]
+        template: NewTemplate,
+
+        /// Create it on a forge and push to it, like `upload`
+        #[clap(long)]
+        upload: bool,
+
+        #[clap(flatten)]
+        opts: UploadOpts,
+    },
+    /// Create a repo for the current directory on a forge and push to it
+    Upload {
+        #[clap(flatten)]
+        opts: UploadOpts,
+    },
+    /// List every cargo package across all cached repos
+    Crates,
+    /// Run cargo/npm outdated on the cached repos and summarize the results
+    Outdated {
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Move repos to another forge and point their origin at the new home
+    Migrate {
+        /// Forge the repos currently live on (only github is supported)
+        #[clap(long)]
+        from: String,
+
+        /// GitLab instance to move them to, e.g. gitlab.com
+        #[clap(long)]
+        to: String,
+
+        /// Archive the source repos once they were pushed to the new forge
+        #[clap(long)]
+        archive_source: bool,
+
+        /// Continue an interrupted migration instead of starting over
+        #[clap(long)]
+        resume: bool,
+
+        /// Don't ask for confirmation
+        #[clap(short, long, alias = "force")]
+        yes: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+    },
+    /// Share the cache with other machines through a git repo or an rsync target
+    SyncCache {
+        /// git remote url, or an rsync destination like host:dir
+        #[clap(long)]
+        via: String,
+    },
+    /// Export an inventory of every cached repo
+    Inventory {
+        #[clap(arg_enum, long, default_value = "json")]
+        format: InventoryFormat,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+    },
+    /// Print the cached repos as a ghq list, a .mrconfig or JSON
+    Export {
+        #[clap(arg_enum, long, default_value = "json")]
+        format: interop::ListFormat,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+    },
+    /// Add the repos of a ghq list, a .mrconfig or a JSON export to the cache
+    Import {
+        /// Read from stdin when left out
+        file: Option<String>,
+
+        #[clap(arg_enum, long, default_value = "json")]
+        format: interop::ListFormat,
+    },
+    /// Write a git bundle of every cached repo and a manifest to restore them from
+    Backup {
+        /// Directory for the bundles, created when missing
+        dest: String,
+
+        /// Only back up repos with commits that no remote has
+        #[clap(long)]
+        unpushed: bool,
+
+        /// Only back up repos without a remote, added to --unpushed when both are given
+        #[clap(long)]
+        no_remote: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Clone the repos of a backup back to where they were, skipping existing directories
+    Restore {
+        /// Directory written by `backup`
+        src: String,
+
+        /// Restore into this directory instead of the original paths
+        #[clap(long)]
+        into: Option<String>,
+    },
+    /// Store the description, stars, fork and archived state and default branch of the repos
+    /// on GitHub in the cache
+    Enrich {
+        #[clap(flatten)]
+        filter: RepoFilter,
+    },
+    /// Manage and verify forge credentials
+    Auth {
+        #[clap(subcommand)]
+        cmd: AuthCmd,
+    },
+    /// Run cargo/npm audit on repos with lockfiles and summarize the vulnerable ones
+    Audit {
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Browse the cached repos interactively
+    Tui,
+    /// Fetch every cached repo, or fast-forward them with --pull
+    Sync {
+        /// Run `git pull --ff-only` instead of `git fetch --all`
+        #[clap(long)]
+        pull: bool,
+
+        /// Continue an interrupted sync instead of starting over
+        #[clap(long)]
+        resume: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Push every ref of the cached repos to a mirror remote, added where it's missing
+    Mirror {
+        /// Name of the mirror remote
+        #[clap(long, default_value = "backup")]
+        remote: String,
+
+        /// Url of each mirror, {owner} and {repo} come from origin or the parent and repo dirs,
+        /// e.g. ssh://backup.host/{owner}/{repo}.git
+        #[clap(long)]
+        url_template: String,
+
+        /// Continue an interrupted mirror instead of starting over
+        #[clap(long)]
+        resume: bool,
+
+        /// Don't ask for confirmation
+        #[clap(short, long, alias = "force")]
+        yes: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// List repos without commits for a while, with their size and unpushed work
+    Stale {
+        /// Age of the latest commit, like 90d, 8w, 6m or 1y
+        #[clap(long, default_value = "1y")]
+        older_than: String,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Search the tracked files of every cached repo, printing path:line:match
+    Grep {
+        pattern: String,
+
+        #[clap(short, long)]
+        ignore_case: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Commits per week and month, the most active repos and totals across the cache
+    Stats {
+        #[clap(arg_enum, long, default_value = "text")]
+        format: stats::StatsFormat,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Calendar of the commits of the last year across the cached repos, like GitHub's
+    Heatmap {
+        /// Only this repo, cached or not
+        #[clap(long)]
+        repo: Option<String>,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Report detached HEADs, missing remotes and upstreams, unreachable hosts and unset
+    /// user.email, with a hint to fix each
+    Doctor {
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// Run a command in every repo, e.g. `pplaces exec -- git gc`
+    Exec {
+        /// Start no more repos once the command failed in one. Without it the command runs in
+        /// every repo whatever happens
+        #[clap(long)]
+        fail_fast: bool,
+
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+
+        #[clap(required = true, last = true)]
+        cmd: Vec<String>,
+    },
+    /// List repos by their size on disk, biggest first
+    Du {
+        #[clap(flatten)]
+        filter: RepoFilter,
+    },
+    /// List remote repos that are cloned in more than one place
+    Dupes,
+    /// List repos with commits that no remote ref contains, oldest unpushed work first
+    Unpushed {
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// List repos with uncommitted changes, unpushed commits or stashes
+    Status {
+        #[clap(flatten)]
+        filter: RepoFilter,
+
+        #[clap(flatten)]
+        bulk: BulkOpts,
+    },
+    /// List repos whose directory name or remote owner/repo contains the query, newest first
+    Find { query: String },
+    /// Print the path of the cached repo that best matches a fuzzy query
+    Jump { query: String },
+    /// Print the `pj` shell function, e.g. `eval "$(pplaces init bash)"`
+    Init {
+        #[clap(arg_enum)]
+        shell: completions::CompletionShell,
+    },
+    /// Print a completion script, e.g. `source <(pplaces completions bash)`
+    Completions {
+        #[clap(arg_enum)]
+        shell: completions::CompletionShell,
+    },
+    /// Words from the cache for the completion scripts
+    #[clap(setting = clap::AppSettings::Hidden)]
+    CompleteRepos {
+        #[clap(arg_enum)]
+        kind: completions::RepoWords,
+    },
 }
 
-/// pplaces helps you manage local git repositories
-#[derive(Parser, Debug)]
-#[clap(author, version, about)]
-struct CliArgs {
-    #[clap(subcommand)]
-    cmd_type: CmdType,
+#[derive(Serialize, Deserialize, Debug, Clone, ArgEnum)]
+enum InventoryFormat {
+    Json,
+    Tsv,
+}
 
-    /// Only show repos with a commit in the last N days
-    #[clap(short, long)]
-    days_to_show: Option<u32>,
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+enum NewTemplate {
+    Rust,
+    Empty,
+}
 
-    /// Show full debug data
-    #[clap(short, long)]
-    full: bool,
+#[derive(Serialize, Deserialize, Debug, Clone, ArgEnum)]
+enum ShowFormat {
+    Json,
+    Tsv,
+    Csv,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ProjectMetadata {
-    path: String,
-    upstream: Vec<String>,
-    latest_commit: Option<NaiveDateTime>,
-    //latest_modification:
+#[derive(Serialize, Deserialize, Debug, Clone, ArgEnum)]
+enum ShowSort {
+    /// Newest commit first
+    Commit,
+    Path,
+    /// Biggest first
+    Size,
 }
 
-struct Config {
-    paths: bool,
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+enum ShowGroup {
+    /// host/owner of the first remote
+    Owner,
+    Host,
+    /// Directory containing the repo
+    ParentDir,
 }
 
-fn scan(path: &Path, cache: &mut Cache) {
-    for e in fs::read_dir(path).unwrap() {
-        let e = e.unwrap();
-        if e.path().is_dir() {
-            if e.path().ends_with(".git") {
-                update_repo_data(&path, cache);
-            } else {
-                scan(&e.path(), cache);
-            }
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, Parser)]
+enum TagCmd {
+    /// Tag the repo at `path`
+    Add {
+        path: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from the repo at `path`
+    Remove {
+        path: String,
+        #[clap(required = true)]
+        tags: Vec<String>,
+    },
+    /// List every tag with the number of repos carrying it
+    List,
 }
 
-fn clone(args: &Vec<String>, data: &Cache) {
-    let url = args
-        .iter()
-        .find(|s| s.starts_with("http") || s.starts_with("git@"))
-        .expect("No url given");
+#[derive(Serialize, Deserialize, Debug, Parser)]
+enum CacheCmd {
+    /// Report entries whose repo is gone or no longer matches what was cached
+    Verify,
+    /// Drop the cache and scan every root again, tags are kept
+    Rebuild {
+        #[clap(flatten)]
+        opts: ScanOpts,
+    },
+    /// Print the cache as JSON, for `cache merge` on another machine
+    Export {
+        /// Write it to this file instead
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Add the repos of an exported cache, the newer commit wins for repos in both
+    Merge { file: String },
+}
 
-    let user_and_repo_name = get_url_ending(url);
+#[derive(Serialize, Deserialize, Debug, Parser)]
+enum RootCmd {
+    /// Remember directories as scan roots
+    Add {
+        #[clap(required = true)]
+        paths: Vec<String>,
+    },
+    /// List the roots, those from config.toml included
+    List,
+    /// Forget a root, its repos stay in the cache until pruned
+    Remove { path: String },
+}
 
-    let repo_matches = data.iter().find(|e| {
-        e.upstream
-            .iter()
-            .any(|url| get_url_ending(url) == user_and_repo_name)
-    });
+#[derive(Serialize, Deserialize, Debug, Parser)]
+enum AuthCmd {
+    /// Test ssh access to every remote host in the cache and validate the API tokens
+    Check,
+    /// Store an API token in the OS keyring
+    Login {
+        /// Forge host the token is for
+        #[clap(long, default_value = "github.com")]
+        host: String,
+    },
+}
 
-    match repo_matches {
-        Some(entry) => println!("{} already exists in\n{}", url, entry.path),
-        None => {
-            let output = Command::new("git")
-                .arg("clone")
-                .args(args)
-                .output()
-                .expect("Failed to run command");
+/// Options shared by the commands that run something in many repos
+#[derive(Serialize, Deserialize, Debug, Args)]
+struct BulkOpts {
+    /// How many repos to work on at the same time
+    #[clap(short, long)]
+    jobs: Option<usize>,
 
-            let stderr = String::from_utf8(output.stderr).unwrap();
-            print!("{stderr}");
-        }
-    }
+    /// Lower the CPU priority by this niceness increment
+    #[clap(long)]
+    nice: Option<i32>,
+
+    /// Lower the IO priority: a best-effort level from 0 to 7, or "idle"
+    #[clap(long)]
+    io_nice: Option<String>,
 }
 
-/// This is O(n)
-fn update_repo_data(path: &Path, cache: &mut Cache) {
-    // We assume that there won't be repetition, so a Vec is just fine.
-    let data = fetch_metadata(path).unwrap();
+/// `jobs`, `nice` and `io_nice` of the config, used for the flags that aren't given
+static BULK_DEFAULTS: OnceLock<BulkOpts> = OnceLock::new();
+
+impl BulkOpts {
+    fn jobs(&self) -> usize {
+        self.jobs
+            .or_else(|| BULK_DEFAULTS.get()?.jobs)
+            .unwrap_or_else(default_jobs)
+    }
+
+    /// Lowers the priority of this process, every command we spawn inherits it.
+    fn apply_priority(&self) -> Result<()> {
+        if let Some(nice) = self.nice.or_else(|| BULK_DEFAULTS.get()?.nice) {
+            set_nice(nice)?;
+        }
 
-    let idx = cache.iter().enumerate().find(|(_, e)| e.path == data.path);
+        let io_nice = self
+            .io_nice
+            .as_ref()
+            .or_else(|| BULK_DEFAULTS.get()?.io_nice.as_ref());
+        if let Some(io_nice) = io_nice {
+            let level = match io_nice.as_str() {
+                "idle" => None,
+                level => match level.parse::<i32>() {
+                    Ok(level @ 0..=7) => Some(level),
+                    _ => bail!("--io-nice and io_nice take a level from 0 to 7 or \"idle\""),
+                },
+            };
+            set_io_nice(level)?;
+        }
 
-    if let Some((i, _)) = idx {
-        cache.swap_remove(i);
+        Ok(())
     }
+}
 
-    cache.push(data);
+#[cfg(unix)]
+fn set_nice(increment: i32) -> Result<()> {
+    unsafe {
+        // -1 is a valid niceness, only errno tells it apart from a failure
+        *errno() = 0;
+        let current = libc::getpriority(libc::PRIO_PROCESS, 0);
+        if current == -1 && *errno() != 0 {
+            bail!(
+                "could not read the niceness: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        if libc::setpriority(libc::PRIO_PROCESS, 0, current + increment) == -1 {
+            bail!(
+                "could not change niceness: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+    Ok(())
 }
 
-fn fetch_metadata(path: &Path) -> Option<ProjectMetadata> {
-    let path_string = path.clone().join(".git").to_str().unwrap().to_owned();
+/// errno of the calling thread
+#[cfg(any(target_os = "linux", target_os = "redox"))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__errno_location()
+}
 
-    let cmd_stdout = Command::new("git")
-        .args(["--git-dir", &path_string, "remote", "-v"])
-        .output()
-        .expect("Failed to run command")
-        .stdout;
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__error()
+}
 
-    let upstreams = String::from_utf8_lossy(&cmd_stdout)
-        .to_string()
-        .split('\n')
-        .map(|s| {
-            if !s.is_empty() {
-                let s = s.split_once('\t').unwrap().1;
-                s.to_owned()
-            } else {
-                s.to_owned()
-            }
-        })
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<String>>();
-
-    let cmd_stdout = Command::new("git")
-        .args(["--git-dir", &path_string, "log", "-n", "1", "--format=%ci"])
-        .output()
-        .expect("Failed to run command")
-        .stdout;
-
-    let str = String::from_utf8_lossy(&cmd_stdout).to_string();
-
-    let date = if !str.is_empty() {
-        let s = str
-            .split(" ")
-            .map(|s| s.to_owned())
-            .collect::<Vec<String>>();
-        let yymmdd = s[0]
-            .split("-")
-            .map(|s| s.to_owned())
-            .collect::<Vec<String>>();
-
-        let y = yymmdd[0].parse::<i32>().unwrap();
-        let month = yymmdd[1].parse::<u32>().unwrap();
-        let d = yymmdd[2].parse::<u32>().unwrap();
-
-        let hhmmss = s[1]
-            .split(":")
-            .map(|s| s.to_owned())
-            .collect::<Vec<String>>();
-        let h = hhmmss[0].parse::<u32>().unwrap();
-        let m = hhmmss[1].parse::<u32>().unwrap();
-        let s = hhmmss[2].parse::<u32>().unwrap();
-
-        let date = NaiveDate::from_ymd(y, month, d).and_hms(h, m, s);
-
-        Some(date)
-    } else {
-        None
-    };
+#[cfg(any(target_os = "android", target_os = "openbsd", target_os = "netbsd"))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::__errno()
+}
 
-    Some(ProjectMetadata {
-        path: path.to_str().unwrap().to_owned(),
-        latest_commit: date,
-        upstream: upstreams,
-    })
+#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+unsafe fn errno() -> *mut libc::c_int {
+    libc::___errno()
 }
 
-fn build_cache(path: &Path) -> Cache {
-    let mut data = match get_cache_from_disk() {
-        Ok(cache) => cache,
-        Err(_) => Vec::new(),
-    };
+#[cfg(not(unix))]
+fn set_nice(_increment: i32) -> Result<()> {
+    bail!("--nice is not supported on this platform")
+}
 
-    scan(path, &mut data);
-    data.sort_by_key(|d| d.latest_commit);
-    data.reverse();
+/// Best-effort IO class at `level`, or the idle class for None. See ioprio_set(2).
+#[cfg(target_os = "linux")]
+fn set_io_nice(level: Option<i32>) -> Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
 
-    data
+    let prio = match level {
+        Some(level) => IOPRIO_CLASS_BE << 13 | level,
+        None => IOPRIO_CLASS_IDLE << 13,
+    };
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, prio) } == -1 {
+        bail!(
+            "could not change IO priority: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok(())
 }
 
-fn config_dir() -> Option<PathBuf> {
-    if let Some(config_dir) = dirs::config_dir() {
-        let config_dir = config_dir.join("pplaces");
-        Some(config_dir)
-    } else {
-        None
-    }
+#[cfg(not(target_os = "linux"))]
+fn set_io_nice(_level: Option<i32>) -> Result<()> {
+    bail!("--io-nice is only supported on linux")
 }
 
-fn save_cache_to_disk(cache: &Cache) {
-    if let Some(config_dir) = config_dir() {
-        fs::create_dir_all(&config_dir).unwrap();
+/// What `upload` creates and where
+#[derive(Serialize, Deserialize, Debug, Args)]
+struct UploadOpts {
+    /// Forge to create the repo on [default: github]
+    #[clap(arg_enum, long)]
+    forge: Option<forge::Forge>,
 
-        // this is written as a JSON because it's easier to interface with web technologies
-        let str = serde_json::to_string(cache).unwrap();
-        fs::write(config_dir.join(".cache.json"), &str).unwrap();
-    }
+    /// Instance of the forge, for self-hosted ones [default: github.com, gitlab.com or
+    /// codeberg.org]
+    #[clap(long)]
+    host: Option<String>,
+
+    /// Create a private repo
+    #[clap(long)]
+    private: bool,
+
+    /// Organization (GitLab group) to create the repo in instead of your account
+    #[clap(long)]
+    org: Option<String>,
+
+    #[clap(long)]
+    description: Option<String>,
 
-    // We don't have an else because it should work even without a disk cache.
+    /// Name of the repo on the forge [default: the directory name]
+    #[clap(long = "name")]
+    repo_name: Option<String>,
+
+    /// Remote to add for the new repo
+    #[clap(long, default_value = "origin")]
+    remote_name: String,
+
+    /// Point the remote at the new repo when it already exists, instead of stopping
+    #[clap(long)]
+    set_url: bool,
+
+    /// Print the API call that would create the repo and stop, without contacting the forge
+    #[clap(long)]
+    dry_run: bool,
 }
 
-fn get_cache_from_disk() -> Result<Cache> {
-    let data_str = fs::read_to_string(config_dir().unwrap().join(".cache.json"))
-        .context("Cache file not found")?;
-    let data = serde_json::from_str::<Cache>(&data_str)?;
+/// Filters for commands that work on many repos at once
+#[derive(Serialize, Deserialize, Debug, Args)]
+struct RepoFilter {
+    /// Only include repos under this directory
+    #[clap(long)]
+    under: Option<String>,
+
+    /// Only include repos whose path contains this text
+    #[clap(long)]
+    matching: Option<String>,
 
-    Ok(data)
+    /// Hand-pick the repos to work on from the filtered list
+    #[clap(long)]
+    select: bool,
+
+    /// Only include the repos whose paths are given on stdin, one per line
+    #[clap(long)]
+    stdin: bool,
+
+    /// Paths on stdin are NUL separated, implies --stdin
+    #[clap(short = '0', long)]
+    null: bool,
 }
 
-fn print_paths(data: &Cache) {
-    for entry in data {
-        println!("{}", entry.path);
+impl RepoFilter {
+    fn matches(&self, e: &ProjectMetadata) -> bool {
+        let under = match &self.under {
+            Some(under) => Path::new(&e.path).starts_with(under),
+            None => true,
+        };
+        let matching = match &self.matching {
+            Some(matching) => e.path.contains(matching.as_str()),
+            None => true,
+        };
+
+        under && matching
     }
-}
 
-fn print_recent(data: &Cache, since: Option<Duration>, location: &Path) {
-    for entry in data.iter().filter(|e| {
-        if e.latest_commit.is_some() {
-            if let Some(date_time) = e.latest_commit {
-                let date_time: DateTime<Local> = Local
-                    .from_local_datetime(&e.latest_commit.unwrap())
-                    .unwrap();
-                let elapsed = Local::now() - date_time;
-
-                let loc_str = location.to_str().unwrap();
-                since.is_none() || (elapsed <= since.unwrap() && e.path.starts_with(loc_str))
-            } else {
-                false
-            }
-        } else {
-            false
+    fn apply(&self, data: &mut Cache) -> Result<()> {
+        data.retain(|e| self.matches(e));
+
+        if self.stdin || self.null {
+            let paths = read_repo_list(self.null)?;
+            data.retain(|e| paths.iter().any(|p| p == Path::new(&e.path)));
         }
-    }) {
-        println!("{}", entry.path);
+
+        if self.select {
+            let picked = pick_repos(&data.iter().map(|e| e.path.as_str()).collect::<Vec<_>>())?;
+            let mut i = 0;
+            data.retain(|_| {
+                i += 1;
+                picked.contains(&(i - 1))
+            });
+        }
+
+        Ok(())
     }
 }
 
-fn get_url_ending(url: &str) -> String {
-    let url = url.split(" ").take(1).collect::<String>();
-    let url = if url.ends_with(".git") {
-        url.split_once(".git").unwrap().0
-    } else {
-        &url
-    };
+/// Reads repo paths from stdin, like the ones printed by `show` or `show -0`
+fn read_repo_list(null: bool) -> Result<Vec<PathBuf>> {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Could not read repo list from stdin")?;
 
-    if url.starts_with("git@") {
-        // SSH repo
-        // git@github.com:gbrls/gdb -FunEnd.git
-        let url = url.split_once(":").unwrap().1;
-        url.into()
-    } else if url.starts_with("http") {
-        // non-ssh repo
-        let url = url.split("/").skip(3).collect::<Vec<_>>();
-        let url = url.join("/");
-        url
-    } else {
-        panic!("{} is not a URL", url);
+    let separator = if null { '\0' } else { '\n' };
+    let paths = input
+        .split(separator)
+        .map(|p| p.trim_end_matches('\r'))
+        .filter(|p| !p.is_empty())
+        .map(|p| fs::canonicalize(p).unwrap_or_else(|_| PathBuf::from(p)))
+        .collect();
+
+    Ok(paths)
+}
+
+/// Interactive checkbox list, returns the indexes of the picked `paths`
+fn pick_repos(paths: &[&str]) -> Result<Vec<usize>> {
+    if !std::io::stdin().is_terminal() {
+        bail!("--select needs an interactive terminal");
     }
+
+    let picked = dialoguer::MultiSelect::new()
+        .with_prompt("Pick repos (space to toggle, enter to confirm)")
+        .items(paths)
+        .interact()?;
+
+    Ok(picked)
 }
 
-async fn upload_repo(path: &Path) -> Result<()> {
-    let repo_name = path.iter().last().unwrap();
-    dbg!(repo_name);
+/// pplaces helps you manage local git repositories
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct CliArgs {
+    #[clap(subcommand)]
+    cmd_type: CmdType,
 
-    //curl -H "Authorization: token $(cat .github-personal-token)" --data '{"name":"teste-api-00"}' https://api.github.com/user/repos
+    /// Only show repos with a commit in the last N days, like `--since Nd`
+    #[clap(short, long)]
+    days_to_show: Option<u32>,
 
-    //git remote add origin git@github.com:USER/REPO.git
-    //git push origin main
+    /// Only show repos with a commit since then: `3d`, `2w`, `6 months`, `1 year ago` or a date
+    /// like 2024-01-31
+    #[clap(long, global = true)]
+    since: Option<String>,
 
-    let token = include_str!("../../.github-personal-token").trim_end();
-    let data = format!("{{\"name\":\"{}\"}}", repo_name.to_str().unwrap());
+    /// Show everything known about each repo instead of its path: remotes, branch, changes,
+    /// the last commit, size and tags
+    #[clap(short, long)]
+    full: bool,
 
-    dbg!(&data, token);
+    /// Talk to forge APIs over http or through gh/glab
+    #[clap(arg_enum, long, default_value = "http")]
+    backend: forge::Backend,
 
-    let https = HttpsConnector::new();
-    let client = Client::builder().build::<_, hyper::Body>(https);
+    /// Where the cache is kept, the SQLite store imports the JSON one on first use
+    #[clap(arg_enum, long, default_value = "json")]
+    store: store::StoreKind,
 
-    let req = Request::builder()
-        .method(Method::POST)
-        //.uri("https://httpbin.org/post")
-        .uri("https://api.github.com/user/repos")
-        .header("content-type", "application/json")
-        .header("User-Agent", "pplaces CLI Tool")
-        .header("Authorization", format!("token {}", token))
-        .body(Body::from(data))?;
-    //.body(Body::from(r#"{"name":"teste-api-01"}"#))?;
+    /// Log what is going on to stderr, -vv also logs every git command and skipped directory
+    #[clap(short, long, global = true, parse(from_occurrences))]
+    verbose: usize,
 
-    let res = client.request(req).await?;
+    /// Only log errors, for scripts
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
 
-    println!("{:#?}", &res);
+/// Logs of pplaces itself go to stderr: warnings, with -v progress and with -vv every git
+/// command and skipped directory
+fn init_logging(verbose: usize, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::WARN,
+        (false, 1) => Level::INFO,
+        (false, 2) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    let filter = Targets::new()
+        .with_target("pplaces", level)
+        .with_target("pplaces_core", level);
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .without_time()
+                .with_target(false),
+        )
+        .with(filter)
+        .init();
+}
 
-    let body = hyper::body::to_bytes(res.into_body()).await?;
-    let body = String::from_utf8(body.to_vec())?;
+/// Date of a commit in the local timezone, "never" without one
+fn local_date(date: Option<DateTime<FixedOffset>>) -> String {
+    date.map(|d| d.with_timezone(&Local).format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "never".into())
+}
 
-    #[derive(serde::Deserialize, Debug)]
-    struct Values {
-        clone_url: String,
-        ssh_url: String,
-    }
+fn is_clone_url(s: &str) -> bool {
+    RemoteUrl::parse(s).is_some()
+}
 
-    let val = from_str::<Values>(&body)?;
+/// The cached repo that has a remote of the same repo as `url`, whatever the protocol and
+/// after the insteadOf rewrites
+fn cloned_at<'a>(data: &'a Cache, url: &str, rewrites: &Rewrites) -> Option<&'a ProjectMetadata> {
+    let key = rewrites.remote(url)?.key();
+    data.iter().find(|e| {
+        e.upstream
+            .iter()
+            .filter_map(|u| rewrites.remote(u))
+            .any(|r| r.key() == key)
+    })
+}
 
-    //git remote add origin https://github.com/gbrls/pplaces.git
-    //git branch -M main
-    //git push -u origin main
+/// Repo names that only differ in case or in `-` and `_`
+fn similar_names(a: &str, b: &str) -> bool {
+    let normal = |s: &str| s.to_lowercase().replace('_', "-");
+    normal(a) == normal(b)
+}
+
+/// A cached repo named like `url` but from another owner, the upstream of a fork or another
+/// fork of it. Only meaningful once `cloned_at` found nothing
+fn same_name_at<'a>(
+    data: &'a Cache,
+    url: &str,
+    rewrites: &Rewrites,
+) -> Option<&'a ProjectMetadata> {
+    let remote = rewrites.remote(url)?;
+    data.iter().find(|e| {
+        e.upstream
+            .iter()
+            .filter_map(|u| rewrites.remote(u))
+            .any(|r| similar_names(r.repo(), remote.repo()))
+    })
+}
 
-    let output = Command::new("git")
-        .args(&["remote", "add", "origin", &val.ssh_url])
-        .output()
-        .expect("Failed to run command");
+/// Where `url` is cloned to when no directory is given: the layout or `clone_layout` from the
+/// config, None for git's default
+fn clone_dest(
+    url: &str,
+    layout: bool,
+    config: &config::Config,
+    rewrites: &Rewrites,
+) -> Option<PathBuf> {
+    let remote = rewrites.remote(url)?;
+    if layout {
+        config.layout_path(&remote.host, &remote.path)
+    } else {
+        config.clone_path(&remote.host, &remote.path)
+    }
+}
 
-    let output = Command::new("git")
-        .args(&["branch", "-M", "main"])
-        .output()
-        .expect("Failed to run command");
+fn clone(
+    args: &[String],
+    layout: bool,
+    yes: bool,
+    data: &mut Cache,
+    config: &config::Config,
+) -> Result<()> {
+    let url = args
+        .iter()
+        .find(|s| is_clone_url(s))
+        .context("No url given")?;
 
-    let output = Command::new("git")
-        .args(&["push", "-u", "origin", "main"])
-        .output()
-        .expect("Failed to run command");
-    //println!("{}", output.stdout);
+    let rewrites = Rewrites::from_git_config();
+    match cloned_at(data, url, &rewrites) {
+        Some(entry) => println!("{} already exists in\n{}", url, entry.path),
+        None => {
+            if let Some(entry) = same_name_at(data, url, &rewrites) {
+                let summary = format!(
+                    "You already have a repo named {} from a different owner at\n{}",
+                    clone_dir_name(url),
+                    entry.path
+                );
+                if !confirm(&summary, yes)? {
+                    return Ok(());
+                }
+            }
+            let mut args = args.to_vec();
+            let mut dest = None;
+            // the url being last means no directory was given
+            if args.last() == Some(url) {
+                dest = clone_dest(url, layout, config, &rewrites);
+                if let Some(dest) = &dest {
+                    args.push(
+                        dest.to_str()
+                            .with_context(|| format!("{dest:?} is not valid UTF-8"))?
+                            .to_owned(),
+                    );
+                }
+            }
+            let dest = match dest {
+                Some(dest) => dest,
+                None if args.last() == Some(url) => PathBuf::from(clone_dir_name(url)),
+                None => PathBuf::from(args.last().context("No url given")?),
+            };
 
-    dbg!(val);
+            info!("Cloning {url} into {}", dest.display());
+            debug!("git clone {}", args.join(" "));
+            let output = Command::new("git")
+                .arg("clone")
+                .args(&args)
+                .output()
+                .context("Failed to run git")?;
 
-    println!("Response Body {body}");
+            print!("{}", String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() {
+                bail!("git clone {url} failed");
+            }
 
-    println!("Uploaded?");
+            // so show and jump know about it without another scan
+            let dest = canonical_path(&dest)
+                .with_context(|| format!("Cloned, but {dest:?} can't be found"))?;
+            update_repo_data(&dest, data)?;
+            let extra = ("PPLACES_CLONE_URL", url.clone());
+            run_repo_hook(config, hooks::Event::PostClone, &dest, data, extra);
+            // the path goes last so a shell wrapper can cd into it
+            if layout {
+                println!("{}", dest.display());
+            }
+        }
+    }
 
     Ok(())
 }
 
-// https://stackoverflow.com/questions/2423777/is-it-possible-to-create-a-remote-repo-on-github-from-the-cli-without-opening-br
-
-// Issues:
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = CliArgs::parse();
+/// Runs the hook of `event` with the cache entry of the repo at `path` and `extra` in its
+/// environment
+fn run_repo_hook(
+    config: &config::Config,
+    event: hooks::Event,
+    path: &Path,
+    data: &Cache,
+    extra: (&'static str, String),
+) {
+    let entry = data.iter().find(|e| Path::new(&e.path) == path);
+    let mut env = hooks::repo_env(path, entry);
+    env.push(extra);
+    hooks::run(&config.hooks, event, &env);
+}
 
-    let days = match args.days_to_show {
-        Some(n) => n,
-        None => 365 * 1_000,
-    };
-    //let days_to_show = Duration::days(days as i64);
-    let days_to_show = None;
-    let full_info = args.full;
+/// Urls of a clone list, skipping blank lines and # comments
+fn parse_clone_list(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(|l| l.split_once('#').map_or(l, |(url, _)| url).trim())
+        .filter(|l| !l.is_empty())
+        .collect()
+}
 
-    match args.cmd_type {
-        CmdType::Scan { ref path } => {
-            let path = Path::new(path);
-            if !path.is_dir() {
-                panic!("{path:?} is not a directory");
-            }
-            // This might be slow in some machines
-            let data = build_cache(path);
-            save_cache_to_disk(&data);
-            print_recent(&data, days_to_show, path);
+/// Clones the urls that aren't cloned yet, `jobs` at a time. `name` is the journal a resumed run
+/// picks up from
+fn clone_list(
+    name: &str,
+    urls: &[&str],
+    layout: bool,
+    jobs: usize,
+    resume: bool,
+    data: &mut Cache,
+    config: &config::Config,
+) -> Result<()> {
+    let log = journal::Journal::open(name, resume)?;
+    let urls = log.pending(urls, |url| *url);
+    let rewrites = Rewrites::from_git_config();
+    let mut todo: Vec<(&str, PathBuf)> = Vec::new();
+    let mut skipped = 0;
+    let mut failed = 0;
+    for &url in &urls {
+        if !is_clone_url(url) {
+            println!("FAIL  {url}: not a url");
+            failed += 1;
+            continue;
         }
-        CmdType::Clone { ref args } => {
-            let data = get_cache_from_disk()?;
-            clone(args, &data);
+        if let Some(entry) = cloned_at(data, url, &rewrites) {
+            println!("skip  {url}: already in {}", entry.path);
+            skipped += 1;
+            continue;
         }
-        CmdType::Show => {
-            let data = get_cache_from_disk()?;
-            if full_info {
-                println!("{data:#?}")
-            } else {
-                print_recent(&data, days_to_show, Path::new("/"));
-            }
+        let dest = clone_dest(url, layout, config, &rewrites)
+            .unwrap_or_else(|| PathBuf::from(clone_dir_name(url)));
+        if dest.exists() || todo.iter().any(|(_, d)| *d == dest) {
+            println!("skip  {url}: {} exists", dest.display());
+            skipped += 1;
+            continue;
+        }
+        if let Some(entry) = same_name_at(data, url, &rewrites) {
+            println!(
+                "note  {url}: another owner's repo of that name is in {}",
+                entry.path
+            );
         }
+        todo.push((url, dest));
+    }
 
-        CmdType::Upload => {
-            //let path = working_directory();
+    info!("Cloning {} repos, {jobs} at a time", todo.len());
+    let log = Mutex::new(log);
+    // a credential prompt from one of many parallel gits can't be answered
+    env::set_var("GIT_TERMINAL_PROMPT", "0");
+    let results = parallel_map(&todo, jobs, |(url, dest)| {
+        let dest_str = dest
+            .to_str()
+            .with_context(|| format!("{dest:?} is not valid UTF-8"))?;
+        run_git(Path::new("."), &["clone", *url, dest_str])?;
+        log.lock().unwrap().record(url, journal::DONE)?;
+        canonical_path(dest).with_context(|| format!("Cloned, but {dest:?} can't be found"))
+    });
 
-            upload_repo(&env::current_dir().unwrap()).await?;
+    let mut cloned = 0;
+    for ((url, _), res) in todo.iter().zip(results) {
+        match res {
+            Ok(dest) => {
+                println!("ok    {url} -> {}", dest.display());
+                update_repo_data(&dest, data)?;
+                let extra = ("PPLACES_CLONE_URL", url.to_string());
+                run_repo_hook(config, hooks::Event::PostClone, &dest, data, extra);
+                cloned += 1;
+            }
+            Err(err) => {
+                println!("FAIL  {url}: {err:#}");
+                failed += 1;
+            }
         }
     }
 
+    println!("Cloned {cloned}, skipped {skipped}, {failed} failed");
+    if failed == 0 {
+        log.into_inner().unwrap().finish()?;
+    }
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Directory git clone picks when none is given: the last part of the url without `.git`
+fn clone_dir_name(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    url.rsplit(['/', ':']).next().unwrap_or(url)
+}
 
-    #[test]
-    fn test_url_parser() {
-        let a = "https://github.com/linebender/runebender (fetch)";
-        let b = "git@github.com:gbrls/Bootloader.git (fetch)";
+/// The cache entry of the repo at `path`, which may be relative
+fn cached_repo<'a>(data: &'a mut Cache, path: &str) -> Result<&'a mut ProjectMetadata> {
+    let path = canonical_path(Path::new(path)).with_context(|| format!("{path} does not exist"))?;
+    data.iter_mut()
+        .find(|e| Path::new(&e.path) == path)
+        .with_context(|| format!("{} is not in the cache, scan it first", path.display()))
+}
+
+/// Removes the entries whose repo is gone from disk, returning their paths
+fn prune_cache(cache: &mut Cache) -> Vec<String> {
+    let mut dropped = Vec::new();
+    cache.retain(|e| {
+        let exists = match &e.git_dir {
+            Some(git_dir) => Path::new(git_dir).is_dir() && Path::new(&e.path).is_dir(),
+            // a file for worktrees and submodules
+            None => Path::new(&e.path).join(".git").exists(),
+        };
+        if !exists {
+            dropped.push(e.path.clone());
+        }
+        exists
+    });
+    dropped
+}
+
+/// What would be lost by deleting the repo: uncommitted changes, stashes and commits that no
+/// remote-tracking branch contains
+fn unsaved_work(path: &Path, git_dir: Option<&Path>) -> Result<Vec<String>> {
+    let st = status::status(path, git_dir)?;
+    let unpushed = status::unpushed(&status::open(path, git_dir)?)?;
 
-        assert_eq!(get_url_ending(a), "linebender/runebender");
-        assert_eq!(get_url_ending(b), "gbrls/Bootloader");
+    let mut work = Vec::new();
+    if st.changed > 0 {
+        work.push(format!("{} changed files", st.changed));
+    }
+    if st.stashes > 0 {
+        work.push(format!("{} stashes", st.stashes));
+    }
+    if unpushed > 0 {
+        work.push(format!("{unpushed} unpushed commits"));
+    }
+    Ok(work)
+}
+
+/// Why the directory at `path` must not be deleted, whatever state the repo in it is in. None
+/// when it can be
+fn protected_dir(path: &Path, roots: &[PathBuf], cache: &Cache) -> Option<String> {
+    if dirs::home_dir().is_some_and(|home| home.starts_with(path)) {
+        return Some("it holds your home directory".to_owned());
+    }
+    let is_root = roots
+        .iter()
+        .any(|r| canonical_path(r).map_or(r == path, |r| r == path));
+    if is_root {
+        return Some("it is a scan root".to_owned());
+    }
+    cache
+        .iter()
+        .map(|e| Path::new(&e.path))
+        .find(|p| *p != path && p.starts_with(path))
+        .map(|nested| format!("the repo {} is inside it", nested.display()))
+}
+
+/// Moves the repo at `path` to the trash and drops its cache entry. Repos whose git dir lives
+/// elsewhere, like a dotfiles repo checked out at $HOME, only lose the git dir and keep their files.
+/// Returns the path of the repo and the entry it had.
+fn remove_repo(
+    path: &Path,
+    force: bool,
+    roots: &[PathBuf],
+    cache: &mut Cache,
+) -> Result<(PathBuf, Option<ProjectMetadata>)> {
+    let path = canonical_path(path).with_context(|| format!("Could not find {path:?}"))?;
+    let path_str = path.to_string_lossy();
+    let entry = cache.iter().find(|e| e.path == path_str).cloned();
+    let git_dir = entry.as_ref().and_then(|e| e.git_dir.clone());
+    let separate_git_dir = git_dir
+        .as_deref()
+        .map(Path::new)
+        .filter(|d| !d.starts_with(&path));
+
+    let doomed = separate_git_dir.unwrap_or(&path);
+    if let Some(why) = protected_dir(doomed, roots, cache) {
+        bail!("Refusing to delete {}, {why}", doomed.display());
+    }
+    if !force {
+        let work = unsaved_work(&path, git_dir.as_deref().map(Path::new))
+            .with_context(|| format!("Could not check {path_str} for unsaved work"))?;
+        if !work.is_empty() {
+            bail!(
+                "{path_str} has {}, pass --force to remove it anyway",
+                work.join(", ")
+            );
+        }
+    }
+
+    trash::put(doomed, entry.clone())
+        .with_context(|| format!("Could not remove {}", doomed.display()))?;
+    cache.retain(|e| e.path != path_str);
+    Ok((path, entry))
+}
+
+/// `path` moved from under `old` to under `new`, None when it isn't under `old`
+fn rebase_path(path: &str, old: &Path, new: &Path) -> Option<String> {
+    let rest = Path::new(path).strip_prefix(old).ok()?;
+    let moved = if rest.as_os_str().is_empty() {
+        new.to_path_buf()
+    } else {
+        new.join(rest)
+    };
+    Some(moved.to_string_lossy().into_owned())
+}
+
+/// Moves the repo at `old` like mv would and points its cache entry, and those of the repos
+/// nested inside it, at the new place. Returns where it ended up.
+fn move_repo(old: &Path, new: &Path, cache: &mut Cache) -> Result<PathBuf> {
+    let old = canonical_path(old).with_context(|| format!("Could not find {old:?}"))?;
+    let new = match old.file_name() {
+        Some(name) if new.is_dir() => new.join(name),
+        _ => new.to_path_buf(),
+    };
+    if new.exists() {
+        bail!("{} already exists", new.display());
+    }
+    if let Some(parent) = new.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&old, &new)
+        .with_context(|| format!("Could not move {} to {}", old.display(), new.display()))?;
+    let new = canonical_path(&new)?;
+
+    for e in cache.iter_mut() {
+        if let Some(path) = rebase_path(&e.path, &old, &new) {
+            e.path = path;
+        }
+        if let Some(git_dir) = e
+            .git_dir
+            .as_deref()
+            .and_then(|d| rebase_path(d, &old, &new))
+        {
+            e.git_dir = Some(git_dir);
+        }
+    }
+
+    let new_str = new.to_string_lossy();
+    let has_worktrees = cache
+        .iter()
+        .any(|e| e.path == new_str && !e.worktrees.is_empty());
+    if has_worktrees {
+        // the linked work trees still point at the old git dir
+        run_git(&new, &["worktree", "repair"])?;
+    }
+    update_repo_data(&new, cache)?;
+
+    Ok(new)
+}
+
+/// [`build_cache`] with a spinner on stderr counting the directories and repos it went through,
+/// when `show` is set
+fn build_cache_showing(
+    data: Cache,
+    paths: &[PathBuf],
+    opts: &ScanOpts,
+    show: bool,
+) -> Result<(Cache, ScanSkips)> {
+    if !show {
+        return build_cache(data, paths, opts);
+    }
+
+    let progress = ScanProgress::default();
+    let done = AtomicBool::new(false);
+    let bar = ProgressBar::new_spinner();
+    bar.set_style(ProgressStyle::with_template("{spinner} {wide_msg}").unwrap());
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            while !done.load(Ordering::Relaxed) {
+                let current = progress.current.lock().unwrap().display().to_string();
+                bar.set_message(format!(
+                    "{} directories, {} repos  {current}",
+                    progress.dirs.load(Ordering::Relaxed),
+                    progress.repos.load(Ordering::Relaxed)
+                ));
+                bar.tick();
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            bar.finish_and_clear();
+        });
+        let res = build_cache_with_progress(data, paths, opts, &progress);
+        done.store(true, Ordering::Relaxed);
+        res
+    })
+}
+
+/// Whether a scan of every root goes into `root`: without `every_days` in its policy always,
+/// otherwise once that many days went by since the last scan recorded in `scanned`
+fn scan_due(scanned: &journal::Journal, root: &Path, every_days: Option<u32>) -> bool {
+    let last = scanned
+        .get(&root.to_string_lossy())
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+    match (every_days, last) {
+        (Some(days), Some(last)) => {
+            Utc::now().signed_duration_since(last) >= Duration::days(days.into())
+        }
+        _ => true,
+    }
+}
+
+/// `137 repos, 12 new, 3 removed, 4.2s`, `before` being the paths cached before the scan
+fn scan_summary(before: &HashSet<String>, after: &Cache, took: std::time::Duration) -> String {
+    let new = after.iter().filter(|e| !before.contains(&e.path)).count();
+    let kept = after.iter().filter(|e| before.contains(&e.path)).count();
+    format!(
+        "{} repos, {new} new, {} removed, {:.1}s",
+        after.len(),
+        before.len() - kept,
+        took.as_secs_f64()
+    )
+}
+
+fn print_pruned(dropped: &[String]) {
+    for path in dropped {
+        println!("Dropped {path}");
+    }
+    println!("Dropped {} repos that are gone from disk", dropped.len());
+}
+
+/// What changed between the cached entry of a repo and a fresh read of it
+fn cache_drift(cached: &ProjectMetadata, fresh: &ProjectMetadata) -> Vec<String> {
+    let mut drift = Vec::new();
+    if cached.current_branch != fresh.current_branch {
+        let name = |b: &Option<String>| b.clone().unwrap_or_else(|| "detached".to_owned());
+        drift.push(format!(
+            "branch {} -> {}",
+            name(&cached.current_branch),
+            name(&fresh.current_branch)
+        ));
+    }
+    if cached.head_commit != fresh.head_commit {
+        drift.push("new commits".to_owned());
+    }
+    if cached.upstream != fresh.upstream {
+        drift.push("remotes changed".to_owned());
+    }
+    if cached.dirty != fresh.dirty {
+        drift.push(
+            if fresh.dirty {
+                "now dirty"
+            } else {
+                "now clean"
+            }
+            .to_owned(),
+        );
+    }
+    drift
+}
+
+/// Checks every entry against its repo, returning how many point at something that is gone or
+/// isn't a repo anymore
+fn verify_cache(data: &Cache, jobs: usize) -> usize {
+    let fresh = parallel_map(data, jobs, |e| {
+        let path = Path::new(&e.path);
+        if !path.is_dir() {
+            return None;
+        }
+        Some(fetch_metadata(path, e.git_dir.as_deref().map(Path::new)))
+    });
+
+    let (mut missing, mut broken, mut drifted) = (0, 0, 0);
+    for (e, fresh) in data.iter().zip(fresh) {
+        match fresh {
+            None => {
+                println!("MISSING  {}", e.path);
+                missing += 1;
+            }
+            Some(Err(err)) => {
+                println!("BROKEN   {}: {err:#}", e.path);
+                broken += 1;
+            }
+            Some(Ok(fresh)) => {
+                let drift = cache_drift(e, &fresh);
+                if !drift.is_empty() {
+                    println!("DRIFT    {}: {}", e.path, drift.join(", "));
+                    drifted += 1;
+                }
+            }
+        }
+    }
+
+    println!(
+        "{} of {} entries are current, {missing} missing, {broken} unreadable, {drifted} out of date",
+        data.len() - missing - broken - drifted,
+        data.len()
+    );
+    if missing + broken + drifted > 0 {
+        println!("`pplaces prune` drops missing repos, `pplaces scan` refreshes the others and `pplaces cache rebuild` starts over");
+    }
+    missing + broken
+}
+
+/// Canonical path and `host/owner/repo` keys an entry is matched by in `merge_caches`
+fn repo_identity(e: &ProjectMetadata) -> (PathBuf, HashSet<String>) {
+    let path = Path::new(&e.path);
+    let keys = e
+        .upstream
+        .iter()
+        .filter_map(|url| sync::remote_key(url))
+        .collect();
+    (
+        canonical_path(path).unwrap_or_else(|_| path.to_path_buf()),
+        keys,
+    )
+}
+
+/// Adds the entries of `theirs` to `ours`, matching them by path or by a shared remote repo.
+/// When both have the same repo the entry with the newer commit wins and the tags of both are
+/// kept. Returns how many entries were added and how many replaced.
+fn merge_caches(ours: &mut Cache, theirs: Cache) -> (usize, usize) {
+    let mut ids = ours.iter().map(repo_identity).collect::<Vec<_>>();
+    let (mut added, mut replaced) = (0, 0);
+    for e in theirs {
+        let (path, keys) = repo_identity(&e);
+        let found = ids
+            .iter()
+            .position(|(p, k)| *p == path || !k.is_disjoint(&keys));
+        match found {
+            Some(i) => {
+                let o = &mut ours[i];
+                let mut tags = o.tags.clone();
+                tags.extend(e.tags.iter().cloned());
+                tags.sort();
+                tags.dedup();
+                if e.latest_commit > o.latest_commit {
+                    *o = e;
+                    ids[i] = (path, keys);
+                    replaced += 1;
+                }
+                o.tags = tags;
+            }
+            None => {
+                ours.push(e);
+                ids.push((path, keys));
+                added += 1;
+            }
+        }
+    }
+    (added, replaced)
+}
+
+/// State that used to be kept next to config.toml
+const OLD_DATA: [&str; 4] = [".cache.json", ".cache.sqlite", "machines", "sync"];
+
+/// Moves the state older versions kept in the config dir to the data dir, once
+fn move_old_data() {
+    let (config, data) = match (config_dir(), data_dir()) {
+        (Some(config), Some(data)) if config != data => (config, data),
+        _ => return,
+    };
+
+    for name in OLD_DATA {
+        let (old, new) = (config.join(name), data.join(name));
+        if !old.exists() || new.exists() {
+            continue;
+        }
+        let moved = fs::create_dir_all(&data).and_then(|_| fs::rename(&old, &new));
+        if let Err(err) = moved {
+            eprintln!(
+                "Could not move {} to {}, move it by hand: {err}",
+                old.display(),
+                new.display()
+            );
+        }
+    }
+}
+
+fn print_recent(
+    data: &Cache,
+    since: Option<Duration>,
+    location: Option<&Path>,
+    projects: bool,
+    null: bool,
+) {
+    for entry in data.iter().filter(|e| {
+        let recent = match (since, e.latest_commit) {
+            (Some(since), Some(date)) => Local::now().signed_duration_since(date) <= since,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        recent && location.is_none_or(|l| Path::new(&e.path).starts_with(l))
+    }) {
+        if null {
+            print!("{}\0", entry.path);
+            continue;
+        }
+        println!("{}", entry.path);
+
+        if projects {
+            for p in &entry.projects {
+                let touched = p
+                    .latest_commit
+                    .map(|d| d.with_timezone(&Local).to_string())
+                    .unwrap_or_else(|| "never".into());
+                println!("  {} ({:?}, {touched})", p.path, p.kind);
+            }
+        }
+    }
+}
+
+/// One repo as printed by `show --format`
+#[derive(Serialize)]
+struct ShowRecord<'a> {
+    path: &'a str,
+    /// Fetch urls of every remote
+    upstreams: Vec<&'a str>,
+    latest_commit: Option<String>,
+    current_branch: Option<&'a str>,
+    default_branch: Option<&'a str>,
+    size_bytes: u64,
+    language: Option<&'a str>,
+    bare: bool,
+}
+
+fn print_records(data: &Cache, format: &ShowFormat) -> Result<()> {
+    let records = data
+        .iter()
+        .map(|e| ShowRecord {
+            path: &e.path,
+            upstreams: e
+                .upstream
+                .iter()
+                .filter_map(|u| u.strip_suffix(" (fetch)"))
+                .collect(),
+            latest_commit: e.latest_commit.map(|d| d.to_rfc3339()),
+            current_branch: e.current_branch.as_deref(),
+            default_branch: e.default_branch.as_deref(),
+            size_bytes: e.size_bytes,
+            language: e.language.as_deref(),
+            bare: e.bare,
+        })
+        .collect::<Vec<_>>();
+
+    match format {
+        ShowFormat::Json => println!("{}", serde_json::to_string_pretty(&records)?),
+        ShowFormat::Tsv => {
+            println!(
+                "path\tupstreams\tlatest_commit\tcurrent_branch\tdefault_branch\tsize_bytes\tlanguage\tbare"
+            );
+            for r in &records {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    r.path,
+                    r.upstreams.join(","),
+                    r.latest_commit.as_deref().unwrap_or_default(),
+                    r.current_branch.unwrap_or_default(),
+                    r.default_branch.unwrap_or_default(),
+                    r.size_bytes,
+                    r.language.unwrap_or_default(),
+                    r.bare
+                );
+            }
+        }
+        ShowFormat::Csv => {
+            println!(
+                "path,upstreams,latest_commit,current_branch,default_branch,size_bytes,language,bare"
+            );
+            for r in &records {
+                println!(
+                    "{},{},{},{},{},{},{},{}",
+                    csv_field(r.path),
+                    csv_field(&r.upstreams.join(" ")),
+                    r.latest_commit.as_deref().unwrap_or_default(),
+                    csv_field(r.current_branch.unwrap_or_default()),
+                    csv_field(r.default_branch.unwrap_or_default()),
+                    r.size_bytes,
+                    csv_field(r.language.unwrap_or_default()),
+                    r.bare
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Quotes a field when it contains a separator, quote or newline (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Highest fuzzy score wins, ties go to the most recently committed repo
+fn best_match<'a>(data: &'a Cache, query: &str) -> Option<&'a ProjectMetadata> {
+    data.iter()
+        .filter_map(|e| fuzzy::score(query, &e.path).map(|s| (s, e)))
+        .max_by_key(|(s, e)| (*s, e.latest_commit))
+        .map(|(_, e)| e)
+}
+
+/// Repos whose directory name or remote `owner/repo` contains `query`, ignoring case.
+/// Most recently committed first.
+fn find_repos<'a>(data: &'a Cache, query: &str) -> Vec<&'a ProjectMetadata> {
+    let query = query.to_lowercase();
+    let mut found = data
+        .iter()
+        .filter(|e| {
+            let name = Path::new(&e.path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            name.contains(&query)
+                || e.upstream
+                    .iter()
+                    .filter_map(|url| RemoteUrl::parse(url))
+                    .any(|r| r.path.to_lowercase().contains(&query))
+        })
+        .collect::<Vec<_>>();
+    found.sort_by_key(|e| std::cmp::Reverse(e.latest_commit));
+    found
+}
+
+fn print_crates(data: &Cache) {
+    let mut packages = Vec::new();
+    let mut patched = Vec::new();
+
+    for entry in data {
+        let path = Path::new(&entry.path);
+        packages.extend(manifest::cargo_packages(path));
+        patched.extend(manifest::cargo_patches(path));
+    }
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.path.cmp(&b.path)));
+
+    let width = packages.iter().map(|p| p.name.len()).max().unwrap_or(0);
+    for p in &packages {
+        let mut flags = String::new();
+        if packages.iter().filter(|o| o.name == p.name).count() > 1 {
+            flags.push_str(" [duplicate]");
+        }
+        if patched.contains(&p.name) {
+            flags.push_str(" [patched]");
+        }
+
+        println!(
+            "{:width$} {:10} {:9} {}{flags}",
+            p.name, p.version, p.edition, p.path
+        );
+    }
+}
+
+/// Runs `check` for every ecosystem found in every repo, returning the repos where it found something.
+/// Failed checks are reported to stderr.
+fn run_dep_checks(
+    data: &Cache,
+    jobs: usize,
+    needs_lockfile: bool,
+    check: fn(&Path, deps::Ecosystem) -> Result<usize>,
+) -> (Vec<(usize, &ProjectMetadata, deps::Ecosystem)>, usize) {
+    let checks = data
+        .iter()
+        .flat_map(|e| {
+            deps::ecosystems(Path::new(&e.path))
+                .into_iter()
+                .filter(|eco| !needs_lockfile || Path::new(&e.path).join(eco.lockfile()).is_file())
+                .map(move |eco| (e, eco))
+        })
+        .collect::<Vec<_>>();
+
+    let results = parallel_map(&checks, jobs, |(e, eco)| check(Path::new(&e.path), *eco));
+
+    let mut found = Vec::new();
+    for ((e, eco), res) in checks.iter().zip(results) {
+        match res {
+            Ok(0) => {}
+            Ok(n) => found.push((n, *e, *eco)),
+            Err(err) => eprintln!("{} check failed in {}: {err}", eco.name(), e.path),
+        }
+    }
+
+    (found, checks.len())
+}
+
+fn print_outdated(data: &Cache, jobs: usize) {
+    let (mut behind, total) = run_dep_checks(data, jobs, false, deps::outdated);
+    behind.sort_by_key(|b| std::cmp::Reverse(b.0));
+
+    for (n, e, eco) in &behind {
+        println!("{n:4} outdated {:5} {}", eco.name(), e.path);
+    }
+    println!(
+        "{} of {} checks found outdated dependencies",
+        behind.len(),
+        total
+    );
+}
+
+fn print_audit(data: &Cache, jobs: usize) {
+    let (mut vulnerable, total) = run_dep_checks(data, jobs, true, deps::audit);
+    // the ones I'm still working on come first
+    vulnerable.sort_by_key(|(_, e, _)| std::cmp::Reverse(e.latest_commit));
+
+    for (n, e, eco) in &vulnerable {
+        let last = local_date(e.latest_commit);
+        println!(
+            "{n:4} vulnerabilities {:5} {} (last commit {last})",
+            eco.name(),
+            e.path
+        );
+    }
+    println!(
+        "{} of {} audited projects have known vulnerabilities",
+        vulnerable.len(),
+        total
+    );
+}
+
+/// Remote-tracking refs that moved and commits HEAD gained while fetching or pulling `path`
+fn sync_repo(path: &Path, pull: bool) -> Result<(usize, usize)> {
+    const REFS: [&str; 3] = [
+        "for-each-ref",
+        "--format=%(refname) %(objectname)",
+        "refs/remotes",
+    ];
+    let before = run_git(path, &REFS)?;
+    let head = run_git(path, &["rev-parse", "HEAD"]).ok();
+
+    if pull {
+        run_git(path, &["pull", "--ff-only"])?;
+    } else {
+        run_git(path, &["fetch", "--all", "--prune"])?;
+    }
+
+    let after = run_git(path, &REFS)?;
+    let moved = after
+        .lines()
+        .filter(|r| !before.lines().any(|b| b == *r))
+        .count();
+    let gained = match head {
+        Some(head) if pull => run_git(path, &["rev-list", "--count", &format!("{head}..HEAD")])?
+            .parse()
+            .unwrap_or(0),
+        _ => 0,
+    };
+
+    Ok((moved, gained))
+}
+
+fn sync_repos(
+    cache: &mut Cache,
+    data: &Cache,
+    pull: bool,
+    jobs: usize,
+    resume: bool,
+) -> Result<()> {
+    let log = journal::Journal::open("sync", resume)?;
+    let data = log.pending(data, |e| e.path.as_str());
+    // recorded as each repo is done, so an interrupted run keeps what it got through
+    let log = Mutex::new(log);
+    // a credential prompt from one of many parallel gits can't be answered
+    env::set_var("GIT_TERMINAL_PROMPT", "0");
+    let results = parallel_map(&data, jobs, |e| -> Result<_> {
+        let synced = sync_repo(Path::new(&e.path), pull)?;
+        log.lock().unwrap().record(&e.path, journal::DONE)?;
+        Ok(synced)
+    });
+
+    let mut updated = Vec::new();
+    let mut synced = HashSet::new();
+    let mut failed = 0;
+    for (e, res) in data.iter().zip(results) {
+        if res.is_ok() {
+            synced.insert(e.path.clone());
+        }
+        match res {
+            Ok((0, 0)) => {}
+            Ok((moved, gained)) => {
+                let mut line = format!("{}  {moved} refs updated", e.path);
+                if gained > 0 {
+                    line.push_str(&format!(", fast-forwarded {gained} commits"));
+                }
+                println!("{line}");
+                updated.push(e.path.clone());
+            }
+            Err(err) => {
+                println!("FAIL  {}: {err:#}", e.path);
+                failed += 1;
+            }
+        }
+    }
+
+    // every fetch moves the last fetch time, and the remote-tracking branches ahead and behind
+    // are counted against
+    refresh_metadata(cache, |e| synced.contains(&e.path));
+    let fresh = cache
+        .iter()
+        .filter(|e| synced.contains(&e.path))
+        .cloned()
+        .collect::<Vec<_>>();
+    store::open()?.upsert(&fresh)?;
+
+    println!(
+        "{} of {} repos received new commits, {failed} failed",
+        updated.len(),
+        data.len()
+    );
+    if failed == 0 {
+        log.into_inner().unwrap().finish()?;
+    }
+    Ok(())
+}
+
+/// Fills in {owner} and {repo} of `template` from the origin url, or without one from the names
+/// of the parent and repo directories
+fn mirror_url(template: &str, path: &Path, origin: Option<&str>) -> String {
+    let dir_name = |p: Option<&Path>| {
+        p.and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let owner = origin
+        .and_then(RemoteUrl::parse)
+        .map(|r| r.owner().to_owned())
+        .unwrap_or_else(|| dir_name(path.parent()));
+    let repo = match origin {
+        Some(url) => clone_dir_name(url).to_owned(),
+        None => dir_name(Some(path)),
+    };
+
+    template.replace("{owner}", &owner).replace("{repo}", &repo)
+}
+
+/// Points `remote` at `template` and pushes all refs there, returning the url
+fn mirror_repo(path: &Path, remote: &str, template: &str) -> Result<String> {
+    let origin = run_git(path, &["remote", "get-url", "origin"]).ok();
+    let url = mirror_url(template, path, origin.as_deref());
+
+    if run_git(path, &["remote", "get-url", remote]).is_ok() {
+        run_git(path, &["remote", "set-url", remote, &url])?;
+    } else {
+        run_git(path, &["remote", "add", remote, &url])?;
+    }
+    run_git(path, &["push", "--mirror", remote])?;
+
+    Ok(url)
+}
+
+fn mirror_repos(
+    cache: &mut Cache,
+    data: &Cache,
+    remote: &str,
+    template: &str,
+    jobs: usize,
+    resume: bool,
+) -> Result<()> {
+    let log = journal::Journal::open("mirror", resume)?;
+    let data = log.pending(data, |e| e.path.as_str());
+    let log = Mutex::new(log);
+    env::set_var("GIT_TERMINAL_PROMPT", "0");
+    let results = parallel_map(&data, jobs, |e| -> Result<_> {
+        let url = mirror_repo(Path::new(&e.path), remote, template)?;
+        log.lock().unwrap().record(&e.path, journal::DONE)?;
+        Ok(url)
+    });
+
+    let mut mirrored = 0;
+    let mut failed = 0;
+    for (e, res) in data.iter().zip(results) {
+        match res {
+            Ok(url) => {
+                println!("{}  -> {url}", e.path);
+                // the new remote shows up in the cached upstreams
+                update_repo_data(Path::new(&e.path), cache)?;
+                mirrored += 1;
+            }
+            Err(err) => {
+                println!("FAIL  {}: {err:#}", e.path);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Mirrored {mirrored} of {} repos to {remote}, {failed} failed",
+        data.len()
+    );
+    if failed == 0 {
+        log.into_inner().unwrap().finish()?;
+    }
+    Ok(())
+}
+
+/// Parses ages like `90d`, `8w`, `6m`, `1y` or spelled out like `6 months` and `2 weeks ago`.
+/// A month is 30 days and a year 365.
+fn parse_age(age: &str) -> Result<Duration> {
+    let trimmed = age.trim();
+    let trimmed = trimmed.strip_suffix(" ago").unwrap_or(trimmed);
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (n, unit) = trimmed.split_at(split);
+    let n = n.parse::<i64>();
+    let days = match (n, unit.trim()) {
+        (Ok(n), "d" | "day" | "days") => n,
+        (Ok(n), "w" | "week" | "weeks") => n * 7,
+        (Ok(n), "m" | "mo" | "month" | "months") => n * 30,
+        (Ok(n), "y" | "year" | "years") => n * 365,
+        _ => bail!("{age:?} is not an age like 90d, 8w, 6m, 1y or 2 weeks ago"),
+    };
+    Ok(Duration::days(days))
+}
+
+/// `--since` takes an age, see [`parse_age`], or a date which counts from its start in local time
+fn parse_since(since: &str, now: DateTime<Local>) -> Result<Duration> {
+    match NaiveDate::parse_from_str(since.trim(), "%Y-%m-%d") {
+        Ok(date) => {
+            let start = date
+                .and_hms_opt(0, 0, 0)
+                .and_then(|d| Local.from_local_datetime(&d).earliest())
+                .with_context(|| format!("{since} doesn't exist in the local timezone"))?;
+            Ok(now.signed_duration_since(start))
+        }
+        Err(_) => parse_age(since),
+    }
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+fn print_du(data: &mut Cache) {
+    data.sort_by_key(|e| std::cmp::Reverse(e.size_bytes));
+
+    for e in data.iter() {
+        let last = local_date(e.latest_commit);
+        println!("{:>10}  {last:10}  {}", human_size(e.size_bytes), e.path);
+    }
+    let total = data.iter().map(|e| e.size_bytes).sum();
+    println!("{} in {} repos", human_size(total), data.len());
+}
+
+/// How many commits `git rev-list --timestamp` listed and when the oldest of them was committed
+fn parse_unpushed(rev_list: &str) -> (usize, Option<DateTime<FixedOffset>>) {
+    let oldest = rev_list
+        .lines()
+        .filter_map(|l| l.split(' ').next()?.parse::<i64>().ok())
+        .min()
+        .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+        .map(|d| d.into());
+    (rev_list.lines().count(), oldest)
+}
+
+fn print_unpushed(data: &Cache, jobs: usize) {
+    let results = parallel_map(data, jobs, |e| {
+        run_git(
+            Path::new(&e.path),
+            &[
+                "rev-list",
+                "--timestamp",
+                "--branches",
+                "--not",
+                "--remotes",
+            ],
+        )
+    });
+
+    let mut unpushed = Vec::new();
+    for (e, res) in data.iter().zip(results) {
+        match res {
+            Ok(out) => match parse_unpushed(&out) {
+                (0, _) => {}
+                (count, oldest) => unpushed.push((oldest, count, e)),
+            },
+            Err(err) => eprintln!("Could not list the commits of {}: {err:#}", e.path),
+        }
+    }
+    // the work that waited the longest first
+    unpushed.sort_by_key(|(oldest, _, _)| *oldest);
+
+    for (oldest, count, e) in &unpushed {
+        println!("{:10}  {count:>5} commits  {}", local_date(*oldest), e.path);
+    }
+    println!(
+        "{} of {} repos have unpushed commits",
+        unpushed.len(),
+        data.len()
+    );
+}
+
+fn print_stale(data: &Cache, older_than: Duration, jobs: usize) {
+    let cutoff: DateTime<FixedOffset> = (Utc::now() - older_than).into();
+    let mut stale = data
+        .iter()
+        .filter(|e| e.latest_commit.is_none_or(|d| d < cutoff))
+        .collect::<Vec<_>>();
+    // oldest first, the ones without commits before everything
+    stale.sort_by_key(|e| e.latest_commit);
+
+    let details = parallel_map(&stale, jobs, |e| {
+        let path = Path::new(&e.path);
+        let status = status::status(path, e.git_dir.as_deref().map(Path::new));
+        (inventory::dir_size(path), status)
+    });
+
+    let mut total = 0;
+    for (e, (size, status)) in stale.iter().zip(details) {
+        total += size;
+        let last = local_date(e.latest_commit);
+        let work = match status {
+            Ok(st) if st.is_clean() => "",
+            Ok(_) => "  [unpushed work]",
+            Err(_) => "  [unreadable]",
+        };
+        println!("{last:10}  {:>10}  {}{work}", human_size(size), e.path);
+    }
+    println!(
+        "{} of {} repos are stale, taking {}",
+        stale.len(),
+        data.len(),
+        human_size(total)
+    );
+}
+
+/// Runs git grep in every repo, printing the matches of each repo as soon as it is done.
+/// Returns how many lines matched.
+fn grep_repos(data: &Cache, pattern: &str, ignore_case: bool, jobs: usize) -> usize {
+    let counts = parallel_map(data, jobs, |e| {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C")
+            .arg(&e.path)
+            .args(["grep", "-n", "-I", "--no-color", "--full-name"]);
+        if ignore_case {
+            cmd.arg("-i");
+        }
+        let output = match cmd.arg("-e").arg(pattern).output() {
+            Ok(output) => output,
+            Err(err) => {
+                eprintln!("Could not run git grep in {}: {err}", e.path);
+                return 0;
+            }
+        };
+        // 1 only means nothing matched
+        if !output.status.success() && output.status.code() != Some(1) {
+            eprintln!(
+                "git grep failed in {}: {}",
+                e.path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return 0;
+        }
+
+        let matches = String::from_utf8_lossy(&output.stdout);
+        let mut out = stdout().lock();
+        let mut count = 0;
+        for line in matches.lines() {
+            if writeln!(out, "{}/{line}", e.path).is_err() {
+                break;
+            }
+            count += 1;
+        }
+        count
+    });
+
+    counts.iter().sum()
+}
+
+/// Runs `cmd` in every repo, printing the output of each one as a block once it is done.
+/// With `fail_fast` the repos that didn't start before the first failure are skipped.
+/// Returns how many failed.
+fn exec_repos(data: &Cache, cmd: &[String], fail_fast: bool, jobs: usize) -> usize {
+    let failed = AtomicBool::new(false);
+    let results = parallel_map(data, jobs, |e| {
+        if fail_fast && failed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let output = Command::new(&cmd[0])
+            .args(&cmd[1..])
+            .current_dir(&e.path)
+            .output();
+
+        let mut out = stdout().lock();
+        let res = match output {
+            Ok(output) => {
+                let _ = writeln!(out, "==> {}", e.path);
+                let _ = out.write_all(&output.stdout);
+                let _ = out.write_all(&output.stderr);
+                output
+                    .status
+                    .code()
+                    .ok_or_else(|| "killed by a signal".to_owned())
+            }
+            Err(err) => {
+                let _ = writeln!(out, "==> {}\nCould not run {}: {err}", e.path, cmd[0]);
+                Err(err.to_string())
+            }
+        };
+        if res != Ok(0) {
+            failed.store(true, Ordering::Relaxed);
+        }
+        Some(res)
+    });
+
+    let mut ok = 0;
+    let mut skipped = 0;
+    let mut failures = Vec::new();
+    for (e, res) in data.iter().zip(results) {
+        match res {
+            Some(Ok(0)) => ok += 1,
+            Some(Ok(code)) => failures.push(format!("exit {code}  {}", e.path)),
+            Some(Err(err)) => failures.push(format!("{err}  {}", e.path)),
+            None => skipped += 1,
+        }
+    }
+
+    println!();
+    for failure in &failures {
+        println!("FAIL  {failure}");
+    }
+    println!(
+        "{ok} succeeded, {} failed, {skipped} skipped",
+        failures.len()
+    );
+    failures.len()
+}
+
+/// Clones of the same remote repo, keyed by `host/owner/repo`, newest commit first
+fn find_dupes(data: &Cache) -> Vec<(String, Vec<&ProjectMetadata>)> {
+    let mut by_remote = std::collections::BTreeMap::<_, Vec<&ProjectMetadata>>::new();
+    for e in data {
+        let mut keys = e
+            .upstream
+            .iter()
+            .filter_map(|url| sync::remote_key(url))
+            .collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            by_remote.entry(key).or_default().push(e);
+        }
+    }
+
+    by_remote
+        .into_iter()
+        .filter(|(_, clones)| clones.len() > 1)
+        .map(|(key, mut clones)| {
+            clones.sort_by_key(|e| std::cmp::Reverse(e.latest_commit));
+            (key, clones)
+        })
+        .collect()
+}
+
+fn print_dupes(data: &Cache) {
+    let dupes = find_dupes(data);
+    for (key, clones) in &dupes {
+        println!("{key}");
+        for (i, e) in clones.iter().enumerate() {
+            let last = local_date(e.latest_commit);
+            let newest = if i == 0 { "  (newest)" } else { "" };
+            println!("  {last:10}  {}{newest}", e.path);
+        }
+    }
+    println!("{} remote repos are cloned more than once", dupes.len());
+}
+
+fn print_status(data: &Cache, jobs: usize) {
+    let results = parallel_map(data, jobs, |e| {
+        status::status(Path::new(&e.path), e.git_dir.as_deref().map(Path::new))
+    });
+
+    let mut unfinished = 0;
+    for (e, res) in data.iter().zip(results) {
+        let st = match res {
+            Ok(st) => st,
+            Err(err) => {
+                eprintln!("Could not read the status of {}: {err:#}", e.path);
+                continue;
+            }
+        };
+
+        let mut parts = Vec::new();
+        if st.changed > 0 {
+            parts.push(format!("{} changed", st.changed));
+        }
+        if let Some((ahead, behind)) = st.ahead_behind {
+            if ahead > 0 {
+                parts.push(format!("{ahead} ahead"));
+            }
+            if behind > 0 {
+                parts.push(format!("{behind} behind"));
+            }
+        }
+        if st.stashes > 0 {
+            parts.push(format!("{} stashed", st.stashes));
+        }
+
+        if !parts.is_empty() {
+            println!("{}  {}", e.path, parts.join(", "));
+        }
+        if !st.is_clean() {
+            unfinished += 1;
+        }
+    }
+    println!("{unfinished} of {} repos have unfinished work", data.len());
+}
+
+fn print_doctor(data: &Cache, jobs: usize) {
+    let results = doctor::check(data, jobs);
+
+    let mut findings = 0;
+    let mut repos = 0;
+    for (e, res) in data.iter().zip(results) {
+        let found = match res {
+            Ok(found) => found,
+            Err(err) => {
+                eprintln!("Could not check {}: {err:#}", e.path);
+                continue;
+            }
+        };
+        if found.is_empty() {
+            continue;
+        }
+
+        println!("{}", e.path);
+        for f in &found {
+            println!("  {}\n      fix: {}", f.describe(), f.hint());
+        }
+        findings += found.len();
+        repos += 1;
+    }
+    println!("{findings} findings in {repos} of {} repos", data.len());
+}
+
+fn print_inventory(data: &Cache, format: &InventoryFormat) -> Result<()> {
+    let assets = parallel_map(data, default_jobs(), |e| {
+        inventory::asset(Path::new(&e.path), e.latest_commit)
+    });
+
+    match format {
+        InventoryFormat::Json => {
+            let inventory = inventory::Inventory {
+                schema_version: inventory::SCHEMA_VERSION,
+                generated_at: Local::now().to_rfc3339(),
+                hostname: hostname(),
+                assets,
+            };
+            println!("{}", serde_json::to_string_pretty(&inventory)?);
+        }
+        InventoryFormat::Tsv => {
+            println!("path\towner\tlicense\tlast_activity\tsize_bytes\tlanguage\tremotes");
+            for a in assets {
+                let remotes = a
+                    .remotes
+                    .iter()
+                    .map(|r| format!("{}={}", r.name, r.url))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{remotes}",
+                    a.path,
+                    a.owner.unwrap_or_default(),
+                    a.license.unwrap_or_default(),
+                    a.last_activity.unwrap_or_default(),
+                    a.size_bytes,
+                    a.language.unwrap_or_default(),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_machine(data: &Cache, machine: &str) -> Result<()> {
+    let theirs = sync::load_machine(machine)?;
+    let here = data
+        .iter()
+        .flat_map(|e| {
+            e.upstream
+                .iter()
+                .filter_map(|url| sync::remote_key(url))
+                .map(move |key| (key, e.path.as_str()))
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let mut missing = 0;
+    for entry in &theirs {
+        let remote = entry
+            .upstream
+            .iter()
+            .find_map(|url| sync::remote_key(url).map(|key| (key, url)));
+        match remote {
+            Some((key, url)) => match here.get(&key) {
+                Some(path) => println!("{}  (here: {path})", entry.path),
+                None => {
+                    let url = url.split(' ').next().unwrap();
+                    println!("{}  not cloned here: {url}", entry.path);
+                    missing += 1;
+                }
+            },
+            None => println!("{}  (no remote)", entry.path),
+        }
+    }
+    println!(
+        "{missing} of {} repos on {machine} are not cloned here",
+        theirs.len()
+    );
+
+    Ok(())
+}
+
+fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0 {
+            let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..end]).into_owned();
+        }
+    }
+
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".into())
+}
+
+/// Creates a repo named after `path`, or `--name`, on the forge and pushes the current branch
+/// there. Fails before creating anything when the forge already has a repo with that name.
+/// Returns the url of the new repo, None for a dry run.
+async fn upload_repo(
+    path: &Path,
+    opts: &UploadOpts,
+    config: &config::Config,
+) -> Result<Option<String>> {
+    let name = match &opts.repo_name {
+        Some(name) => name.as_str(),
+        None => path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .with_context(|| format!("{path:?} has no usable name"))?,
+    };
+    let forge = opts.forge.or(config.forge).unwrap_or(forge::Forge::Github);
+    let host = opts
+        .host
+        .clone()
+        .or_else(|| config.forge_host.clone())
+        .unwrap_or_else(|| forge.default_host().to_owned());
+    let repo = forge::NewRepo {
+        name,
+        org: opts.org.as_deref(),
+        private: opts.private,
+        description: opts.description.as_deref(),
+    };
+
+    if opts.dry_run {
+        let (method, uri, body) = forge.create_request(&host, &repo)?;
+        println!("{method} {uri}\n{}", serde_json::to_string_pretty(&body)?);
+        return Ok(None);
+    }
+
+    let branch = status::open(path, None)
+        .ok()
+        .and_then(|repo| status::current_branch(&repo))
+        .context("No branch is checked out, there is nothing to push")?;
+    let remote = opts.remote_name.as_str();
+    // checked before anything is created on the forge
+    let existing = run_git(path, &["remote", "get-url", remote]).ok();
+    if let (Some(existing), false) = (&existing, opts.set_url) {
+        bail!(
+            "Remote {remote} already points at {existing}, pass --remote-name <name> to add \
+             another one or --set-url to replace it"
+        );
+    }
+    if let Some(taken) = forge.existing_repo(&host, &repo).await? {
+        bail!(
+            "{name} already exists on {host} as {taken}, pass --name <other> to use another name"
+        );
+    }
+
+    info!("Creating {name} on {host}");
+    let url = forge.create_repo(&host, &repo).await?;
+    println!("Created {url}");
+
+    let set_remote = if existing.is_some() {
+        run_git(path, &["remote", "set-url", remote, &url])
+    } else {
+        run_git(path, &["remote", "add", remote, &url])
+    };
+    // the forge repo exists by now, say so along with what went wrong
+    set_remote.with_context(|| format!("Created {url}, but could not set remote {remote}"))?;
+    info!("Pushing {branch} to {remote}");
+    run_git(path, &["push", "-u", remote, &branch])
+        .with_context(|| format!("Created {url}, but could not push {branch} to it"))?;
+    println!("Pushed {branch} of {name} to {url}");
+
+    Ok(Some(url))
+}
+
+/// Files of a new repo made from `template`, paths relative to the repo
+fn template_files(template: NewTemplate, name: &str) -> Vec<(&'static str, String)> {
+    let mut files = vec![("README.md", format!("# {name}\n"))];
+    match template {
+        NewTemplate::Rust => {
+            files.push((
+                "Cargo.toml",
+                format!(
+                    "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[dependencies]\n"
+                ),
+            ));
+            files.push((
+                "src/main.rs",
+                "fn main() {\n    println!(\"Hello, world!\");\n}\n".to_owned(),
+            ));
+            files.push((".gitignore", "/target\n".to_owned()));
+        }
+        NewTemplate::Empty => {}
+    }
+    files
+}
+
+/// Creates `name` under the default root with the files of `template` and commits them
+fn new_repo(name: &str, template: NewTemplate, config: &config::Config) -> Result<PathBuf> {
+    let path = config.default_root().join(name);
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+
+    for (file, contents) in template_files(template, name) {
+        let file = path.join(file);
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&file, contents).with_context(|| format!("Could not write {file:?}"))?;
+    }
+    run_git(&path, &["init"])?;
+    run_git(&path, &["add", "-A"])?;
+    run_git(&path, &["commit", "-m", "Initial commit"])?;
+
+    Ok(canonical_path(&path)?)
+}
+
+/// Prints what is about to happen and asks before doing it. `yes` skips the question,
+/// without it we refuse to go on when nobody is there to answer.
+fn confirm(summary: &str, yes: bool) -> Result<bool> {
+    println!("{summary}");
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        bail!("Refusing to continue without confirmation, pass --yes to skip it");
+    }
+
+    print!("Continue? [y/N] ");
+    stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Header `e` is listed under with `show --group-by`
+fn group_key(e: &ProjectMetadata, by: ShowGroup) -> String {
+    let remote = e
+        .upstream
+        .iter()
+        .find_map(|u| u.strip_suffix(" (fetch)"))
+        .and_then(RemoteUrl::parse);
+    let key = match by {
+        ShowGroup::Owner => remote.map(|r| format!("{}/{}", r.host, r.owner())),
+        ShowGroup::Host => remote.map(|r| r.host),
+        ShowGroup::ParentDir => Path::new(&e.path).parent().map(|p| p.display().to_string()),
+    };
+    key.unwrap_or_else(|| "(no remote)".to_owned())
+}
+
+/// Groups in alphabetical order, the repos keep the order they had
+fn print_grouped(data: &Cache, by: ShowGroup) {
+    let mut groups = BTreeMap::<_, Vec<_>>::new();
+    for e in data {
+        groups.entry(group_key(e, by)).or_default().push(e);
+    }
+
+    for (key, repos) in &groups {
+        println!("{key} ({})", repos.len());
+        for e in repos {
+            println!("  {}", e.path);
+        }
+    }
+}
+
+/// Whether a fetch url of `e` is on `host` and belongs to `owner`, None matches anything
+fn has_remote_on(e: &ProjectMetadata, host: Option<&str>, owner: Option<&str>) -> bool {
+    e.upstream
+        .iter()
+        .filter_map(|u| u.strip_suffix(" (fetch)"))
+        .filter_map(RemoteUrl::parse)
+        .any(|r| {
+            let host_ok = host.is_none_or(|host| r.host.eq_ignore_ascii_case(host));
+            let owner_ok = owner.is_none_or(|owner| r.owner().eq_ignore_ascii_case(owner));
+            host_ok && owner_ok
+        })
+}
+
+/// `user@host` and port for ssh remotes, None for other protocols
+fn get_ssh_target(url: &str) -> Option<(String, Option<String>)> {
+    let remote = RemoteUrl::parse(url).filter(|r| r.scheme == "ssh")?;
+    let target = match remote.user {
+        Some(user) => format!("{user}@{}", remote.host),
+        None => remote.host,
+    };
+    Some((target, remote.port.map(|p| p.to_string())))
+}
+
+/// Runs `ssh -T` against a git host, forges answer with a greeting instead of a shell.
+fn check_ssh(target: &str, port: Option<&str>) -> Result<()> {
+    let mut cmd = Command::new("ssh");
+    cmd.args(["-T", "-o", "BatchMode=yes", "-o", "ConnectTimeout=10"]);
+    if let Some(port) = port {
+        cmd.args(["-p", port]);
+    }
+    let output = cmd.arg(target).output().context("Failed to run ssh")?;
+
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // github exits with 1 even when the key was accepted
+    if output.status.success()
+        || text.contains("successfully authenticated")
+        || text.contains("Welcome")
+    {
+        Ok(())
+    } else {
+        bail!("{}", text.lines().last().unwrap_or("ssh failed").trim())
+    }
+}
+
+/// `owner/repo` of the first remote of `e` on github.com
+fn github_repo_of(e: &ProjectMetadata) -> Option<String> {
+    e.upstream
+        .iter()
+        .filter_map(|u| u.strip_suffix(" (fetch)"))
+        .filter_map(RemoteUrl::parse)
+        .find(|r| r.host == "github.com")
+        .map(|r| r.path)
+}
+
+async fn enrich_repos(cache: &mut Cache, data: &Cache) -> Result<()> {
+    let mut enriched = 0;
+    let mut failed = 0;
+    for e in data {
+        let owner_repo = match github_repo_of(e) {
+            Some(owner_repo) => owner_repo,
+            None => continue,
+        };
+        match forge::github_repo(&owner_repo).await {
+            Ok(repo) => {
+                let info = forge::RemoteInfo::from(repo);
+                let mut flags = String::new();
+                if info.fork {
+                    flags.push_str("  fork");
+                }
+                if info.archived {
+                    flags.push_str("  archived");
+                }
+                println!("{:>6}  {}{flags}", info.stars, e.path);
+                if let Some(entry) = cache.iter_mut().find(|c| c.path == e.path) {
+                    entry.remote_info = Some(info);
+                }
+                enriched += 1;
+            }
+            Err(err) => {
+                println!("FAIL  {}: {err:#}", e.path);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Enriched {enriched} repos from GitHub, {failed} failed");
+    Ok(())
+}
+
+async fn auth_check(data: &Cache) -> Result<()> {
+    let mut targets = data
+        .iter()
+        .flat_map(|e| e.upstream.iter())
+        .filter_map(|url| get_ssh_target(url))
+        .collect::<Vec<_>>();
+    targets.sort();
+    targets.dedup();
+
+    let results = parallel_map(&targets, default_jobs(), |(target, port)| {
+        check_ssh(target, port.as_deref())
+    });
+
+    let mut failed = 0;
+    for ((target, port), res) in targets.iter().zip(results) {
+        let port = port.as_ref().map(|p| format!(":{p}")).unwrap_or_default();
+        match res {
+            Ok(()) => println!("ok    ssh {target}{port}"),
+            Err(e) => {
+                println!("FAIL  ssh {target}{port}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    let mut api_hosts = vec!["github.com".to_owned()];
+    api_hosts.extend(
+        data.iter()
+            .flat_map(|e| e.upstream.iter())
+            .filter_map(|url| RemoteUrl::parse(url))
+            .map(|r| r.host)
+            .filter(|host| host.contains("gitlab"))
+            .filter(|host| {
+                forge::gitlab_token(host).is_ok() || forge::backend() == forge::Backend::Cli
+            }),
+    );
+    api_hosts.sort();
+    api_hosts.dedup();
+
+    for host in api_hosts {
+        let user = if host == "github.com" {
+            forge::github_user().await
+        } else {
+            forge::gitlab_user(&host).await
+        };
+        match user {
+            Ok(user) => println!("ok    api {host} (authenticated as {user})"),
+            Err(e) => {
+                println!("FAIL  api {host}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} checks failed");
+    }
+    Ok(())
+}
+
+async fn migrate(
+    cache: &mut Cache,
+    filter: &RepoFilter,
+    from: &str,
+    to: &str,
+    archive_source: bool,
+    resume: bool,
+    yes: bool,
+) -> Result<()> {
+    let from_host = match from {
+        "github" | "github.com" => "github.com",
+        _ => bail!("only github is supported as a migration source"),
+    };
+
+    let mut selected = cache.clone();
+    selected.retain(|e| {
+        e.upstream
+            .iter()
+            .filter_map(|url| RemoteUrl::parse(url))
+            .any(|r| r.host == from_host)
+    });
+    filter.apply(&mut selected)?;
+
+    let repos = selected
+        .iter()
+        .filter_map(|e| {
+            let origin = run_git(Path::new(&e.path), &["remote", "get-url", "origin"]).ok()?;
+            let on_host = RemoteUrl::parse(&origin).is_some_and(|r| r.host == from_host);
+            on_host.then(|| (e.path.clone(), origin))
+        })
+        .collect::<Vec<_>>();
+
+    let mut summary = repos
+        .iter()
+        .map(|(path, origin)| format!("  {path} ({origin})\n"))
+        .collect::<String>();
+    summary.push_str(&format!("will be moved to {to} and their origin rewritten"));
+    if archive_source {
+        summary.push_str(", the GitHub repos will be archived");
+    }
+    if repos.is_empty() || !confirm(&summary, yes)? {
+        return Ok(());
+    }
+    let mut journal = journal::Journal::open("migrate", resume)?;
+
+    let mut migrated = Vec::new();
+    let mut failed = 0;
+    for (path, origin) in &repos {
+        match migrate_repo(Path::new(path), origin, to, archive_source, &mut journal).await {
+            Ok(target) => {
+                println!("{path} -> {target}");
+                migrated.push(path.clone());
+            }
+            Err(e) => {
+                eprintln!("Could not migrate {path}: {e:#}");
+                failed += 1;
+            }
+        }
+    }
+
+    for path in &migrated {
+        update_repo_data(Path::new(path), cache)?;
+    }
+
+    println!("Migrated {} repos, {failed} failed", migrated.len());
+    if failed == 0 {
+        journal.finish()?;
+    }
+    Ok(())
+}
+
+/// Creates the project on the target forge, pushes a mirror of the source and rewrites origin.
+/// Each step is journaled so a resumed run skips what already happened.
+async fn migrate_repo(
+    path: &Path,
+    origin: &str,
+    to: &str,
+    archive_source: bool,
+    journal: &mut journal::Journal,
+) -> Result<String> {
+    let key = path.to_str().unwrap();
+    let owner_repo = RemoteUrl::parse(origin)
+        .with_context(|| format!("{origin} is not a remote url"))?
+        .path;
+
+    let (stage, target) = match journal.get(key).and_then(|s| s.split_once(' ')) {
+        Some((stage, target)) => (stage.to_owned(), target.to_owned()),
+        None => {
+            let source = forge::github_repo(&owner_repo).await?;
+            let repo = forge::NewRepo {
+                name: &source.name,
+                org: None,
+                private: source.private,
+                description: source.description.as_deref(),
+            };
+            let target = forge::Forge::Gitlab.create_repo(to, &repo).await?;
+            journal.record(key, &format!("created {target}"))?;
+            ("created".to_owned(), target)
+        }
+    };
+
+    if stage == "created" {
+        // a mirror clone carries every branch and tag, not just what is checked out locally
+        let name = owner_repo.rsplit('/').next().unwrap();
+        let mirror = env::temp_dir().join(format!("pplaces-migrate-{name}.git"));
+        let _ = fs::remove_dir_all(&mirror);
+        let mirror_str = mirror.to_str().unwrap();
+        let pushed = run_git(path, &["clone", "--mirror", origin, mirror_str])
+            .and_then(|_| run_git(&mirror, &["push", "--mirror", &target]));
+        let _ = fs::remove_dir_all(&mirror);
+        pushed?;
+        journal.record(key, &format!("pushed {target}"))?;
+    }
+
+    if archive_source {
+        forge::github_archive(&owner_repo).await?;
+    }
+    run_git(path, &["remote", "set-url", "origin", &target])?;
+    journal.record(key, &format!("done {target}"))?;
+
+    Ok(target)
+}
+
+// https://stackoverflow.com/questions/2423777/is-it-possible-to-create-a-remote-repo-on-github-from-the-cli-without-opening-br
+
+// Issues:
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = CliArgs::parse();
+    init_logging(args.verbose, args.quiet);
+
+    let config = config::load()?;
+    let _ = BULK_DEFAULTS.set(BulkOpts {
+        jobs: config.jobs,
+        nice: config.nice,
+        io_nice: config.io_nice.clone(),
+    });
+    let since = match &args.since {
+        Some(_) if args.days_to_show.is_some() => {
+            bail!("--since and --days-to-show can't be used together")
+        }
+        Some(since) => Some(parse_since(since, Local::now())?),
+        None => args
+            .days_to_show
+            .or(config.days_to_show)
+            .map(|n| Duration::days(n as i64)),
+    };
+    let full_info = args.full;
+    // nobody watches a spinner when the output goes to a pipe
+    let show_progress = !args.quiet && stdout().is_terminal();
+    forge::set_backend(args.backend);
+    store::set_kind(args.store);
+    move_old_data();
+
+    match args.cmd_type {
+        CmdType::Scan {
+            ref paths,
+            prune,
+            ref opts,
+        } => {
+            let every_root = paths.is_empty();
+            let paths = match paths.as_slice() {
+                [] => roots::all(&config)?,
+                paths => paths.iter().map(PathBuf::from).collect(),
+            };
+            if paths.is_empty() {
+                bail!("No path given and no roots, see `pplaces root add`");
+            }
+            let paths = paths
+                .iter()
+                .map(|p| {
+                    canonical_path(p)
+                        .ok()
+                        .filter(|p| p.is_dir())
+                        .with_context(|| format!("{p:?} is not a directory"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for root in roots::add(&paths)? {
+                println!("Remembering {} as a root", root.display());
+            }
+            let mut scanned = journal::Journal::open("scanned", true)?;
+            let paths = paths
+                .into_iter()
+                .filter(|p| {
+                    let every_days = config.policy(p).every_days;
+                    let due = !every_root || opts.force || scan_due(&scanned, p, every_days);
+                    if !due {
+                        println!(
+                            "Skipping {}, it was scanned less than {} days ago",
+                            p.display(),
+                            every_days.unwrap_or_default()
+                        );
+                    }
+                    due
+                })
+                .collect::<Vec<_>>();
+            // This might be slow in some machines
+            let opts = ScanOpts {
+                exclude: config.excludes(&opts.exclude),
+                ..opts.clone()
+            };
+            let started = std::time::Instant::now();
+            let cache = get_cache_from_disk().unwrap_or_default();
+            let before = cache.iter().map(|e| e.path.clone()).collect::<HashSet<_>>();
+            let mut data = cache.clone();
+            let mut skips = ScanSkips::default();
+            for path in &paths {
+                let opts = config.policy(path).apply(&opts);
+                let (found, skipped) =
+                    build_cache_showing(data, std::slice::from_ref(path), &opts, show_progress)?;
+                data = found;
+                skips.add(skipped);
+            }
+            if prune {
+                print_pruned(&prune_cache(&mut data));
+            }
+            // a clone or tag while scanning went to the store, not into `data`
+            merge_cache_to_disk(&cache, &mut data)?;
+            let now = Utc::now().to_rfc3339();
+            for path in &paths {
+                scanned.record(&path.to_string_lossy(), &now)?;
+            }
+            // with several roots the location filter would hide all but one of them
+            let location = match paths.as_slice() {
+                [path] => Some(path.as_path()),
+                _ => None,
+            };
+            print_recent(&data, since, location, false, false);
+            println!("{}", scan_summary(&before, &data, started.elapsed()));
+            let new = data.iter().filter(|e| !before.contains(&e.path)).count();
+            let scanned_roots = env::join_paths(&paths).unwrap_or_default();
+            let vars = [
+                (
+                    "PPLACES_SCAN_ROOTS",
+                    scanned_roots.to_string_lossy().into_owned(),
+                ),
+                ("PPLACES_SCAN_REPOS", data.len().to_string()),
+                ("PPLACES_SCAN_NEW", new.to_string()),
+            ];
+            hooks::run(&config.hooks, hooks::Event::PostScan, &vars);
+            if skips.too_deep > 0 {
+                println!(
+                    "Stopped at the max depth below the roots, {} directories were not entered",
+                    skips.too_deep
+                );
+            }
+            if skips.other_fs > 0 {
+                println!(
+                    "Skipped {} mount points on other filesystems",
+                    skips.other_fs
+                );
+            }
+            if !skips.errors.is_empty() {
+                bail!(
+                    "{} paths could not be scanned, see the messages above",
+                    skips.errors.len()
+                );
+            }
+        }
+        CmdType::Watch { ref path } => {
+            let roots = match path {
+                Some(path) => vec![PathBuf::from(path)],
+                None => roots::all(&config)?,
+            };
+            if roots.is_empty() {
+                bail!("No path given and no roots, see `pplaces root add`");
+            }
+            let opts = ScanOpts {
+                exclude: config.excludes(&[]),
+                ..ScanOpts::default()
+            };
+            let roots = roots
+                .into_iter()
+                .filter(|r| config.policy(r).watch)
+                .map(|r| {
+                    let opts = config.policy(&r).apply(&opts);
+                    (r, opts)
+                })
+                .collect::<Vec<_>>();
+            if roots.is_empty() {
+                bail!("Every root has watch = false in its policy");
+            }
+            watch::watch(&roots)?;
+        }
+        CmdType::Tag { ref cmd } => {
+            let mut data = get_cache_from_disk()?;
+            match cmd {
+                TagCmd::Add { path, tags } => {
+                    let entry = cached_repo(&mut data, path)?;
+                    for tag in tags {
+                        if !entry.tags.contains(tag) {
+                            entry.tags.push(tag.clone());
+                        }
+                    }
+                    entry.tags.sort();
+                    save_cache_to_disk(&data)?;
+                }
+                TagCmd::Remove { path, tags } => {
+                    let entry = cached_repo(&mut data, path)?;
+                    entry.tags.retain(|t| !tags.contains(t));
+                    save_cache_to_disk(&data)?;
+                }
+                TagCmd::List => {
+                    let mut counts = BTreeMap::<_, usize>::new();
+                    for tag in data.iter().flat_map(|e| &e.tags) {
+                        *counts.entry(tag).or_default() += 1;
+                    }
+                    for (tag, n) in counts {
+                        println!("{tag} ({n})");
+                    }
+                }
+            }
+        }
+        CmdType::Root { ref cmd } => match cmd {
+            RootCmd::Add { paths } => {
+                let paths = paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+                for root in roots::add(&paths)? {
+                    println!("Added {}", root.display());
+                }
+            }
+            RootCmd::List => {
+                let from_config = config.roots();
+                for root in roots::all(&config)? {
+                    if from_config.contains(&root) {
+                        println!("{}  (config.toml)", root.display());
+                    } else {
+                        println!("{}", root.display());
+                    }
+                }
+            }
+            RootCmd::Remove { path } => {
+                if !roots::remove(Path::new(path))? {
+                    bail!("{path} is not a registered root");
+                }
+            }
+        },
+        CmdType::Cache { ref cmd } => match cmd {
+            CacheCmd::Verify => {
+                let data = get_cache_from_disk()?;
+                if verify_cache(&data, default_jobs()) > 0 {
+                    std::process::exit(1);
+                }
+            }
+            CacheCmd::Export { output } => {
+                let json = store::to_json(&get_cache_from_disk()?)?;
+                match output {
+                    Some(file) => {
+                        fs::write(file, json).with_context(|| format!("Could not write {file}"))?
+                    }
+                    None => println!("{json}"),
+                }
+            }
+            CacheCmd::Merge { file } => {
+                let text =
+                    fs::read_to_string(file).with_context(|| format!("Could not read {file}"))?;
+                let theirs =
+                    store::from_json(&text).with_context(|| format!("Invalid cache {file}"))?;
+                let mut data = get_cache_from_disk().unwrap_or_default();
+                let (added, replaced) = merge_caches(&mut data, theirs);
+                save_cache_to_disk(&data)?;
+                println!("Added {added} repos and updated {replaced} from {file}");
+            }
+            CacheCmd::Rebuild { opts } => {
+                let paths = roots::all(&config)?
+                    .into_iter()
+                    .filter(|p| p.is_dir())
+                    .collect::<Vec<_>>();
+                if paths.is_empty() {
+                    bail!("No roots to scan, see `pplaces root add`");
+                }
+                let old = get_cache_from_disk().unwrap_or_default();
+                let opts = ScanOpts {
+                    exclude: config.excludes(&opts.exclude),
+                    // nothing is known, every repo is read again
+                    force: true,
+                    ..opts.clone()
+                };
+                let mut data = Vec::new();
+                let mut skips = ScanSkips::default();
+                for path in &paths {
+                    let opts = config.policy(path).apply(&opts);
+                    let (found, skipped) = build_cache_showing(
+                        data,
+                        std::slice::from_ref(path),
+                        &opts,
+                        show_progress,
+                    )?;
+                    data = found;
+                    skips.add(skipped);
+                }
+                for e in &mut data {
+                    if let Some(old) = old.iter().find(|o| o.path == e.path) {
+                        keep_user_fields(e, old);
+                    }
+                }
+                merge_cache_to_disk(&old, &mut data)?;
+                println!(
+                    "Rebuilt the cache from {} roots, {} repos (there were {})",
+                    paths.len(),
+                    data.len(),
+                    old.len()
+                );
+                if !skips.errors.is_empty() {
+                    bail!(
+                        "{} paths could not be scanned, see the messages above",
+                        skips.errors.len()
+                    );
+                }
+            }
+        },
+        CmdType::Prune => {
+            let mut data = get_cache_from_disk()?;
+            let dropped = prune_cache(&mut data);
+            save_cache_to_disk(&data)?;
+            print_pruned(&dropped);
+        }
+        CmdType::Remove { ref path, force } => {
+            let mut data = get_cache_from_disk()?;
+            let (removed, entry) =
+                remove_repo(Path::new(path), force, &roots::all(&config)?, &mut data)?;
+            save_cache_to_disk(&data)?;
+            println!("Moved {path} to the trash, `pplaces undo` puts it back");
+            let vars = hooks::repo_env(&removed, entry.as_ref());
+            hooks::run(&config.hooks, hooks::Event::RepoRemoved, &vars);
+            let purged = trash::purge(config.trash_days.unwrap_or(trash::DEFAULT_DAYS))?;
+            if purged > 0 {
+                println!("Emptied {purged} old removals from the trash");
+            }
+        }
+        CmdType::Undo { ref path, list } => {
+            if list {
+                for r in trash::list()? {
+                    let removed = r.removed.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+                    println!("{removed}  {}", r.from.display());
+                }
+                return Ok(());
+            }
+            let path = path.as_deref().map(std::path::absolute).transpose()?;
+            let removal = trash::undo(path.as_deref())?;
+            if let Some(entry) = removal.entry {
+                store::open()?.upsert(std::slice::from_ref(&entry))?;
+                let mut data = get_cache_from_disk()?;
+                update_repo_data(Path::new(&entry.path), &mut data)?;
+            }
+            println!("Restored {}", removal.from.display());
+        }
+        CmdType::Mv {
+            ref old_path,
+            ref new_path,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            let moved = move_repo(Path::new(old_path), Path::new(new_path), &mut data)?;
+            save_cache_to_disk(&data)?;
+            println!("Moved {old_path} to {}", moved.display());
+        }
+        CmdType::Add {
+            ref git_dir,
+            ref work_tree,
+        } => {
+            let mut data = get_cache_from_disk().unwrap_or_default();
+            register_repo(Path::new(work_tree), Path::new(git_dir), &mut data)?;
+            save_cache_to_disk(&data)?;
+        }
+        CmdType::Clone {
+            layout,
+            ref from_file,
+            yes,
+            resume,
+            ref bulk,
+            ref args,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            match from_file {
+                Some(file) => {
+                    let list = fs::read_to_string(file)
+                        .with_context(|| format!("Could not read {file}"))?;
+                    bulk.apply_priority()?;
+                    let urls = parse_clone_list(&list);
+                    let jobs = bulk.jobs();
+                    clone_list("clone", &urls, layout, jobs, resume, &mut data, &config)?;
+                }
+                None => clone(args, layout, yes, &mut data, &config)?,
+            }
+        }
+        CmdType::CloneAll {
+            ref account,
+            https,
+            resume,
+            ref bulk,
+        } => {
+            let name = match account.split_once('/') {
+                Some(("github.com", name)) => name.trim_end_matches('/'),
+                Some((host, _)) => bail!("only github.com is supported, {host} is not"),
+                None => bail!("Expected github.com/<user or org>, got {account}"),
+            };
+            let repos = forge::github_account_repos(name).await?;
+            let urls = repos
+                .iter()
+                .map(|r| {
+                    if https {
+                        r.clone_url.as_str()
+                    } else {
+                        r.ssh_url.as_str()
+                    }
+                })
+                .collect::<Vec<_>>();
+            println!("{name} has {} repos", urls.len());
+
+            let mut data = get_cache_from_disk()?;
+            bulk.apply_priority()?;
+            let jobs = bulk.jobs();
+            clone_list("clone-all", &urls, true, jobs, resume, &mut data, &config)?;
+        }
+        CmdType::Show {
+            has_docker,
+            projects,
+            null,
+            refresh,
+            ref machine,
+            ref format,
+            dirty,
+            unpushed,
+            with_stashes,
+            forks,
+            forks_behind_upstream,
+            unreleased,
+            mine_only,
+            ref not_fetched_since,
+            ref sort,
+            group_by,
+            table,
+            plain,
+            ref lang,
+            ref host,
+            ref owner,
+            no_remote,
+            ref tag,
+            ref workspace,
+            ref repo,
+        } => {
+            let mut data = match since {
+                Some(days) => store::open()?.recent(Utc::now() - days)?,
+                None => get_cache_from_disk()?,
+            };
+            if let Some(machine) = machine {
+                print_machine(&data, machine)?;
+                return Ok(());
+            }
+            if let Some(repo) = repo {
+                let path = cached_repo(&mut data, repo)?.path.clone();
+                data.retain(|e| e.path == path);
+            }
+            if refresh {
+                refresh_metadata(&mut data, |e| !has_docker || e.has_docker);
+                // with --days-to-show this is only part of the cache
+                store::open()?.upsert(&data)?;
+            }
+            if has_docker {
+                data.retain(|e| e.has_docker);
+            }
+            if dirty {
+                data.retain(|e| e.dirty);
+            }
+            if unpushed {
+                data.retain(|e| e.unpushed > 0);
+            }
+            if with_stashes {
+                data.retain(|e| e.stashes > 0);
+            }
+            if forks {
+                data.retain(|e| e.fork_of.is_some());
+            }
+            if forks_behind_upstream {
+                data.retain(|e| e.parent_behind.is_some_and(|b| b > 0));
+            }
+            if unreleased {
+                data.retain(|e| e.latest_tag.is_some() && e.commits_since_tag > 0);
+            }
+            if mine_only {
+                data.retain(|e| e.my_commits != Some(0));
+            }
+            if let Some(age) = not_fetched_since {
+                let cutoff = Utc::now() - parse_since(age, Local::now())?;
+                data.retain(|e| !e.upstream.is_empty() && e.last_fetch.is_none_or(|t| t < cutoff));
+            }
+            if let Some(lang) = lang {
+                data.retain(|e| {
+                    e.language
+                        .as_ref()
+                        .is_some_and(|l| l.eq_ignore_ascii_case(lang))
+                });
+            }
+            if host.is_some() || owner.is_some() {
+                data.retain(|e| has_remote_on(e, host.as_deref(), owner.as_deref()));
+            }
+            if no_remote {
+                data.retain(|e| e.upstream.is_empty());
+            }
+            if let Some(tag) = tag {
+                data.retain(|e| e.tags.contains(tag));
+            }
+            if let Some(name) = workspace {
+                let workspace = config
+                    .workspaces
+                    .get(name)
+                    .with_context(|| format!("No workspace {name} in config.toml"))?;
+                data.retain(|e| workspace.contains(Path::new(&e.path), &e.tags));
+            }
+            match sort {
+                Some(ShowSort::Commit) => data.sort_by_key(|e| std::cmp::Reverse(e.latest_commit)),
+                Some(ShowSort::Path) => data.sort_by(|a, b| a.path.cmp(&b.path)),
+                Some(ShowSort::Size) => data.sort_by_key(|e| std::cmp::Reverse(e.size_bytes)),
+                None => {}
+            }
+            if let Some(format) = format {
+                print_records(&data, format)?;
+            } else if let Some(group_by) = group_by {
+                print_grouped(&data, group_by);
+            } else if table {
+                let theme = (!plain && theme::use_color())
+                    .then(|| theme::Theme::new(&config.theme))
+                    .transpose()?;
+                table::print(&data, theme.as_ref());
+            } else if full_info || repo.is_some() {
+                for (i, e) in data.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    print!("{}", details::render(e));
+                }
+            } else {
+                print_recent(&data, since, None, projects, null);
+            }
+        }
+
+        CmdType::New {
+            ref name,
+            template,
+            upload,
+            ref opts,
+        } => {
+            let path = new_repo(name, template, &config)?;
+            println!("Created {}", path.display());
+            let url = if upload {
+                upload_repo(&path, opts, &config).await?
+            } else {
+                None
+            };
+            let mut data = get_cache_from_disk().unwrap_or_default();
+            update_repo_data(&path, &mut data)?;
+            if let Some(url) = url {
+                let extra = ("PPLACES_UPLOAD_URL", url);
+                run_repo_hook(&config, hooks::Event::PostUpload, &path, &data, extra);
+            }
+            // the path goes last so a shell wrapper can cd into it
+            println!("{}", path.display());
+        }
+        CmdType::Upload { ref opts } => {
+            let path = env::current_dir()?;
+            if let Some(url) = upload_repo(&path, opts, &config).await? {
+                let mut data = get_cache_from_disk().unwrap_or_default();
+                update_repo_data(&path, &mut data)?;
+                let extra = ("PPLACES_UPLOAD_URL", url);
+                run_repo_hook(&config, hooks::Event::PostUpload, &path, &data, extra);
+            }
+        }
+        CmdType::Crates => {
+            let data = get_cache_from_disk()?;
+            print_crates(&data);
+        }
+        CmdType::Outdated {
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_outdated(&data, bulk.jobs());
+        }
+        CmdType::Migrate {
+            ref from,
+            ref to,
+            archive_source,
+            resume,
+            yes,
+            ref filter,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            migrate(&mut data, filter, from, to, archive_source, resume, yes).await?;
+        }
+        CmdType::SyncCache { ref via } => {
+            let data = get_cache_from_disk()?;
+            let machines = sync::sync(via, &data)?;
+            if machines.is_empty() {
+                println!("Published the cache, no other machines have synced yet");
+            } else {
+                println!("Synced with {}", machines.join(", "));
+            }
+        }
+        CmdType::Inventory {
+            ref format,
+            ref filter,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            print_inventory(&data, format)?;
+        }
+        CmdType::Export { format, ref filter } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            print!("{}", interop::export(&data, format)?);
+        }
+        CmdType::Import { ref file, format } => {
+            let (text, mr_dir) = match file {
+                Some(file) => {
+                    let text = fs::read_to_string(file)
+                        .with_context(|| format!("Could not read {file}"))?;
+                    let dir = Path::new(file).parent().map(|d| d.to_path_buf());
+                    (text, dir)
+                }
+                None => {
+                    let mut text = String::new();
+                    std::io::stdin().read_to_string(&mut text)?;
+                    (text, None)
+                }
+            };
+            let base = match format {
+                interop::ListFormat::Ghq => interop::ghq_root(),
+                // mr sections are relative to the .mrconfig, usually in home
+                _ => mr_dir
+                    .filter(|d| !d.as_os_str().is_empty())
+                    .or_else(dirs::home_dir)
+                    .unwrap_or_default(),
+            };
+
+            let mut data = get_cache_from_disk().unwrap_or_default();
+            let mut imported = 0;
+            let mut missing = 0;
+            for path in interop::import(&text, format, &base)? {
+                let is_repo = path.join(".git").exists() || is_bare_repo(&path);
+                match canonical_path(&path) {
+                    Ok(path) if is_repo => {
+                        update_repo_data(&path, &mut data)?;
+                        imported += 1;
+                    }
+                    _ => {
+                        println!("Not cloned here: {}", path.display());
+                        missing += 1;
+                    }
+                }
+            }
+            println!("Imported {imported} repos, {missing} are not cloned here");
+        }
+        CmdType::Backup {
+            ref dest,
+            unpushed,
+            no_remote,
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            if unpushed || no_remote {
+                data.retain(|e| {
+                    (unpushed && e.unpushed > 0) || (no_remote && e.upstream.is_empty())
+                });
+            }
+            bulk.apply_priority()?;
+
+            let failed = backup::backup(&data, Path::new(dest), bulk.jobs())?;
+            for (path, err) in &failed {
+                println!("FAIL  {path}: {err:#}");
+            }
+            println!(
+                "Backed up {} of {} repos to {dest}",
+                data.len() - failed.len(),
+                data.len()
+            );
+            if !failed.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        CmdType::Restore { ref src, ref into } => {
+            let src = Path::new(src);
+            let manifest = backup::load_manifest(src)?;
+            let into = into.as_deref().map(Path::new);
+
+            let mut data = get_cache_from_disk().unwrap_or_default();
+            let mut restored = 0;
+            for entry in &manifest.repos {
+                let target = backup::target(entry, into);
+                if target.exists() {
+                    println!("Exists, skipping: {}", target.display());
+                    continue;
+                }
+                match backup::restore(src, entry, &target) {
+                    Ok(()) => {
+                        update_repo_data(&canonical_path(&target)?, &mut data)?;
+                        println!("Restored {}", target.display());
+                        restored += 1;
+                    }
+                    Err(err) => println!("FAIL  {}: {err:#}", target.display()),
+                }
+            }
+            println!(
+                "Restored {restored} of {} repos from the backup of {}",
+                manifest.repos.len(),
+                manifest.created
+            );
+        }
+        CmdType::Enrich { ref filter } => {
+            let mut cache = get_cache_from_disk()?;
+            let mut data = cache.clone();
+            filter.apply(&mut data)?;
+            let base = cache.clone();
+            enrich_repos(&mut cache, &data).await?;
+            merge_cache_to_disk(&base, &mut cache)?;
+        }
+        CmdType::Auth { ref cmd } => match cmd {
+            AuthCmd::Check => {
+                let data = get_cache_from_disk()?;
+                auth_check(&data).await?;
+            }
+            AuthCmd::Login { host } => {
+                let token = dialoguer::Password::new()
+                    .with_prompt(format!("API token for {host}"))
+                    .interact()?;
+                forge::store_token(host, &token)?;
+                println!("Stored the token for {host} in the keyring");
+            }
+        },
+        CmdType::Audit {
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_audit(&data, bulk.jobs());
+        }
+        CmdType::Tui => {
+            let data = get_cache_from_disk()?;
+            tui::run(data)?;
+        }
+        CmdType::Mirror {
+            ref remote,
+            ref url_template,
+            resume,
+            yes,
+            ref filter,
+            ref bulk,
+        } => {
+            let mut cache = get_cache_from_disk()?;
+            let mut data = cache.clone();
+            filter.apply(&mut data)?;
+            let mut summary = data
+                .iter()
+                .map(|e| format!("  {}\n", e.path))
+                .collect::<String>();
+            // --mirror also deletes the refs the mirror has and the repo doesn't
+            summary.push_str(&format!(
+                "will have {remote} set to {url_template} and every ref of the mirror replaced"
+            ));
+            if data.is_empty() || !confirm(&summary, yes)? {
+                return Ok(());
+            }
+            bulk.apply_priority()?;
+            mirror_repos(&mut cache, &data, remote, url_template, bulk.jobs(), resume)?;
+        }
+        CmdType::Sync {
+            pull,
+            resume,
+            ref filter,
+            ref bulk,
+        } => {
+            let mut cache = get_cache_from_disk()?;
+            let mut data = cache.clone();
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            sync_repos(&mut cache, &data, pull, bulk.jobs(), resume)?;
+        }
+        CmdType::Stale {
+            ref older_than,
+            ref filter,
+            ref bulk,
+        } => {
+            let older_than = parse_age(older_than)?;
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_stale(&data, older_than, bulk.jobs());
+        }
+        CmdType::Grep {
+            ref pattern,
+            ignore_case,
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = match since {
+                Some(days) => store::open()?.recent(Utc::now() - days)?,
+                None => get_cache_from_disk()?,
+            };
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            if grep_repos(&data, pattern, ignore_case, bulk.jobs()) == 0 {
+                std::process::exit(1);
+            }
+        }
+        CmdType::Stats {
+            format,
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            stats::print(&stats::collect(&data, bulk.jobs()), format)?;
+        }
+        CmdType::Heatmap {
+            ref repo,
+            ref filter,
+            ref bulk,
+        } => {
+            let paths = match repo {
+                Some(repo) => vec![PathBuf::from(repo)],
+                None => {
+                    let mut data = get_cache_from_disk()?;
+                    filter.apply(&mut data)?;
+                    data.into_iter().map(|e| PathBuf::from(e.path)).collect()
+                }
+            };
+            bulk.apply_priority()?;
+
+            let year_ago = Local::now() - Duration::days(365);
+            let dates = parallel_map(&paths, bulk.jobs(), |p| {
+                stats::commit_dates(p, year_ago).unwrap_or_default()
+            })
+            .concat();
+
+            let theme = theme::use_color()
+                .then(|| theme::Theme::new(&config.theme))
+                .transpose()?;
+            print!(
+                "{}",
+                heatmap::render(&dates, Local::now().naive_local().date(), theme.as_ref())
+            );
+            println!(
+                "{} commits in the last year across {} repos    {}",
+                dates.len(),
+                paths.len(),
+                heatmap::legend()
+            );
+        }
+        CmdType::Doctor {
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_doctor(&data, bulk.jobs());
+        }
+        CmdType::Exec {
+            fail_fast,
+            ref filter,
+            ref bulk,
+            ref cmd,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            if exec_repos(&data, cmd, fail_fast, bulk.jobs()) > 0 {
+                std::process::exit(1);
+            }
+        }
+        CmdType::Du { ref filter } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            print_du(&mut data);
+        }
+        CmdType::Dupes => {
+            let data = get_cache_from_disk()?;
+            print_dupes(&data);
+        }
+        CmdType::Unpushed {
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_unpushed(&data, bulk.jobs());
+        }
+        CmdType::Status {
+            ref filter,
+            ref bulk,
+        } => {
+            let mut data = get_cache_from_disk()?;
+            filter.apply(&mut data)?;
+            bulk.apply_priority()?;
+            print_status(&data, bulk.jobs());
+        }
+        CmdType::Find { ref query } => {
+            let data = get_cache_from_disk()?;
+            for e in find_repos(&data, query) {
+                println!("{}", e.path);
+            }
+        }
+        CmdType::Jump { ref query } => {
+            let data = get_cache_from_disk()?;
+            match best_match(&data, query) {
+                Some(e) => println!("{}", e.path),
+                None => bail!("No cached repo matches {query}"),
+            }
+        }
+        CmdType::Init { shell } => {
+            print!("{}", completions::init_script(shell));
+        }
+        CmdType::Completions { shell } => {
+            print!("{}", completions::script(shell));
+        }
+        CmdType::CompleteRepos { kind } => {
+            let data = get_cache_from_disk().unwrap_or_default();
+            for word in completions::repo_words(&data, kind)? {
+                println!("{word}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebase_path() {
+        let (old, new) = (Path::new("/src/tool"), Path::new("/archive/tool"));
+        assert_eq!(
+            rebase_path("/src/tool", old, new).as_deref(),
+            Some("/archive/tool")
+        );
+        assert_eq!(
+            rebase_path("/src/tool/vendor/lib", old, new).as_deref(),
+            Some("/archive/tool/vendor/lib")
+        );
+        assert_eq!(rebase_path("/src/tools", old, new), None);
+    }
+
+    #[test]
+    fn test_protected_dir() {
+        let repo = |path: &str| ProjectMetadata {
+            path: path.to_owned(),
+            ..Default::default()
+        };
+        let cache = vec![
+            repo("/src/tool"),
+            repo("/src/tool/vendor/lib"),
+            repo("/src/app"),
+        ];
+        let roots = [PathBuf::from("/src")];
+
+        assert!(protected_dir(Path::new("/src/app"), &roots, &cache).is_none());
+        assert!(protected_dir(Path::new("/src/tool"), &roots, &cache).is_some());
+        assert!(protected_dir(Path::new("/src"), &roots, &cache).is_some());
+        assert!(protected_dir(&dirs::home_dir().unwrap(), &roots, &cache).is_some());
+        assert!(protected_dir(Path::new("/"), &[], &Cache::new()).is_some());
+    }
+
+    #[test]
+    fn test_mirror_url() {
+        let template = "ssh://backup.host/{owner}/{repo}.git";
+        assert_eq!(
+            mirror_url(
+                template,
+                Path::new("/src/pplaces"),
+                Some("git@github.com:gbrls/pplaces.git")
+            ),
+            "ssh://backup.host/gbrls/pplaces.git"
+        );
+        assert_eq!(
+            mirror_url(template, Path::new("/home/me/notes"), None),
+            "ssh://backup.host/me/notes.git"
+        );
+    }
+
+    #[test]
+    fn test_parse_clone_list() {
+        let list = "# work\nhttps://github.com/gbrls/pplaces\n\n  git@github.com:a/b.git  # fork\n";
+        assert_eq!(
+            parse_clone_list(list),
+            ["https://github.com/gbrls/pplaces", "git@github.com:a/b.git"]
+        );
+    }
+
+    #[test]
+    fn test_template_files() {
+        let files = template_files(NewTemplate::Rust, "scratch");
+        let cargo = files.iter().find(|(f, _)| *f == "Cargo.toml").unwrap();
+        assert!(cargo.1.contains("name = \"scratch\""));
+        assert!(files.iter().any(|(f, _)| *f == "src/main.rs"));
+
+        let files = template_files(NewTemplate::Empty, "scratch");
+        assert_eq!(files, [("README.md", "# scratch\n".to_owned())]);
+    }
+
+    #[test]
+    fn test_clone_dir_name() {
+        assert_eq!(
+            clone_dir_name("https://github.com/gbrls/pplaces"),
+            "pplaces"
+        );
+        assert_eq!(
+            clone_dir_name("git@github.com:gbrls/pplaces.git"),
+            "pplaces"
+        );
+        assert_eq!(clone_dir_name("https://example.com/a/b.git/"), "b");
+    }
+
+    #[test]
+    fn test_same_name_at() {
+        let data = vec![ProjectMetadata {
+            path: "/src/rust-analyzer".to_owned(),
+            upstream: vec!["https://github.com/rust-lang/rust-analyzer (fetch)".to_owned()],
+            ..Default::default()
+        }];
+        let rewrites = Rewrites::default();
+        let fork = same_name_at(&data, "git@github.com:someone/Rust_Analyzer.git", &rewrites);
+        assert_eq!(fork.map(|e| e.path.as_str()), Some("/src/rust-analyzer"));
+        assert!(same_name_at(&data, "https://github.com/someone/rustfmt", &rewrites).is_none());
+        assert!(cloned_at(&data, "git@github.com:rust-lang/rust-analyzer", &rewrites).is_some());
+    }
+
+    #[test]
+    fn test_group_key() {
+        let repo = ProjectMetadata {
+            path: "/src/gbrls/pplaces".to_owned(),
+            upstream: vec![
+                "git@github.com:gbrls/pplaces.git (fetch)".to_owned(),
+                "git@github.com:gbrls/pplaces.git (push)".to_owned(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(group_key(&repo, ShowGroup::Owner), "github.com/gbrls");
+        assert_eq!(group_key(&repo, ShowGroup::Host), "github.com");
+        assert_eq!(group_key(&repo, ShowGroup::ParentDir), "/src/gbrls");
+
+        let local = ProjectMetadata {
+            path: "/src/notes".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(group_key(&local, ShowGroup::Owner), "(no remote)");
+    }
+
+    #[test]
+    fn test_github_repo_of() {
+        let repo = |urls: &[&str]| ProjectMetadata {
+            upstream: urls.iter().map(|u| format!("{u} (fetch)")).collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            github_repo_of(&repo(&[
+                "git@gitlab.com:gbrls/pplaces.git",
+                "https://github.com/gbrls/pplaces.git"
+            ]))
+            .as_deref(),
+            Some("gbrls/pplaces")
+        );
+        assert_eq!(
+            github_repo_of(&repo(&["git@gitlab.com:gbrls/pplaces.git"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_best_match() {
+        let repo = |path: &str, date: Option<&str>| ProjectMetadata {
+            path: path.to_owned(),
+            upstream: Vec::new(),
+            latest_commit: date.map(|d| d.parse().unwrap()),
+            ..ProjectMetadata::default()
+        };
+        let data = vec![
+            repo("/src/old/pplaces", Some("2020-01-01T00:00:00+00:00")),
+            repo("/src/pplaces", Some("2022-01-01T00:00:00+00:00")),
+            repo("/src/places-app", None),
+        ];
+
+        assert_eq!(best_match(&data, "ppl").unwrap().path, "/src/pplaces");
+        assert_eq!(best_match(&data, "old").unwrap().path, "/src/old/pplaces");
+        assert!(best_match(&data, "xyz").is_none());
+    }
+
+    #[test]
+    fn test_find_dupes() {
+        let repo = |path: &str, url: &str, date: &str| ProjectMetadata {
+            path: path.to_owned(),
+            upstream: vec![format!("{url} (fetch)"), format!("{url} (push)")],
+            latest_commit: Some(date.parse().unwrap()),
+            ..ProjectMetadata::default()
+        };
+        let data = vec![
+            repo(
+                "/old/pplaces",
+                "https://github.com/gbrls/pplaces",
+                "2020-01-01T00:00:00+00:00",
+            ),
+            repo(
+                "/src/other",
+                "git@github.com:gbrls/other.git",
+                "2021-01-01T00:00:00+00:00",
+            ),
+            repo(
+                "/src/pplaces",
+                "git@github.com:gbrls/pplaces.git",
+                "2022-01-01T00:00:00+00:00",
+            ),
+        ];
+
+        let dupes = find_dupes(&data);
+        assert_eq!(dupes.len(), 1);
+        assert_eq!(dupes[0].0, "github.com/gbrls/pplaces");
+        let paths = dupes[0]
+            .1
+            .iter()
+            .map(|e| e.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/src/pplaces", "/old/pplaces"]);
+    }
+
+    #[test]
+    fn test_scan_summary() {
+        let before = ["/src/a", "/src/b", "/src/gone"]
+            .map(|p| p.to_owned())
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let after = ["/src/a", "/src/b", "/src/new"]
+            .map(|p| ProjectMetadata {
+                path: p.to_owned(),
+                ..Default::default()
+            })
+            .to_vec();
+        assert_eq!(
+            scan_summary(&before, &after, std::time::Duration::from_millis(4200)),
+            "3 repos, 1 new, 1 removed, 4.2s"
+        );
+    }
+
+    #[test]
+    fn test_cache_drift() {
+        let cached = ProjectMetadata {
+            current_branch: Some("main".to_owned()),
+            head_commit: Some("3f2a".to_owned()),
+            ..Default::default()
+        };
+        assert!(cache_drift(&cached, &cached.clone()).is_empty());
+
+        let fresh = ProjectMetadata {
+            current_branch: None,
+            head_commit: Some("9c1b".to_owned()),
+            dirty: true,
+            ..cached.clone()
+        };
+        assert_eq!(
+            cache_drift(&cached, &fresh),
+            ["branch main -> detached", "new commits", "now dirty"]
+        );
+    }
+
+    #[test]
+    fn test_merge_caches() {
+        let date = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap());
+        let entry = |path: &str, url: &str, commit: &str, tag: &str| ProjectMetadata {
+            path: path.to_owned(),
+            upstream: vec![format!("{url} (fetch)")],
+            latest_commit: date(commit),
+            tags: vec![tag.to_owned()],
+            ..Default::default()
+        };
+        let mut ours = vec![
+            entry(
+                "/desktop/a",
+                "git@github.com:me/a.git",
+                "2024-01-02T00:00:00Z",
+                "work",
+            ),
+            entry(
+                "/desktop/b",
+                "git@github.com:me/b.git",
+                "2024-01-02T00:00:00Z",
+                "old",
+            ),
+        ];
+        let theirs = vec![
+            // same remote repo cloned somewhere else, with newer commits
+            entry(
+                "/laptop/a",
+                "https://github.com/me/a",
+                "2024-02-01T00:00:00Z",
+                "laptop",
+            ),
+            entry(
+                "/desktop/b",
+                "git@github.com:me/b.git",
+                "2023-12-01T00:00:00Z",
+                "new",
+            ),
+            entry(
+                "/laptop/c",
+                "git@github.com:me/c.git",
+                "2024-01-01T00:00:00Z",
+                "c",
+            ),
+        ];
+
+        assert_eq!(merge_caches(&mut ours, theirs), (1, 1));
+        assert_eq!(ours.len(), 3);
+        assert_eq!(ours[0].path, "/laptop/a");
+        assert_eq!(ours[0].tags, ["laptop", "work"]);
+        assert_eq!(ours[1].latest_commit, date("2024-01-02T00:00:00Z"));
+        assert_eq!(ours[1].tags, ["new", "old"]);
+        assert_eq!(ours[2].path, "/laptop/c");
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("90d").unwrap(), Duration::days(90));
+        assert_eq!(parse_age("2w").unwrap(), Duration::days(14));
+        assert_eq!(parse_age("1y").unwrap(), Duration::days(365));
+        assert_eq!(parse_age("6 months").unwrap(), Duration::days(180));
+        assert_eq!(parse_age("2 weeks ago").unwrap(), Duration::days(14));
+        assert_eq!(parse_age("1 day").unwrap(), Duration::days(1));
+        assert!(parse_age("1").is_err());
+        assert!(parse_age("y").is_err());
+        assert!(parse_age("").is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let now = Local
+            .from_local_datetime(&"2024-01-11T00:00:00".parse().unwrap())
+            .unwrap();
+        assert_eq!(parse_since("2024-01-01", now).unwrap(), Duration::days(10));
+        assert_eq!(parse_since("3d", now).unwrap(), Duration::days(3));
+    }
+
+    #[test]
+    fn test_parse_unpushed() {
+        let out = "1704067200 3f2a\n1700000000 9c1b\n1710000000 77de";
+        let (count, oldest) = parse_unpushed(out);
+        assert_eq!(count, 3);
+        assert_eq!(oldest.unwrap().timestamp(), 1700000000);
+        assert_eq!(parse_unpushed(""), (0, None));
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_find_repos() {
+        let repo = |path: &str, url: &str, date: &str| ProjectMetadata {
+            path: path.to_owned(),
+            upstream: vec![format!("{url} (fetch)")],
+            latest_commit: Some(date.parse().unwrap()),
+            ..ProjectMetadata::default()
+        };
+        let data = vec![
+            repo(
+                "/src/fork",
+                "git@github.com:gbrls/PPlaces.git",
+                "2020-01-01T00:00:00+00:00",
+            ),
+            repo(
+                "/src/pplaces",
+                "/mnt/backup/pplaces",
+                "2022-01-01T00:00:00+00:00",
+            ),
+            repo(
+                "/src/other",
+                "https://github.com/someone/other",
+                "2021-01-01T00:00:00+00:00",
+            ),
+        ];
+
+        let paths = |query| {
+            find_repos(&data, query)
+                .iter()
+                .map(|e| e.path.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paths("pplaces"), ["/src/pplaces", "/src/fork"]);
+        assert_eq!(paths("someone/"), ["/src/other"]);
+        assert!(paths("backup").is_empty());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("/src/pplaces"), "/src/pplaces");
+        assert_eq!(csv_field("/src/a,b"), "\"/src/a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_ssh_target() {
+        assert_eq!(get_ssh_target("https://github.com/a/b (fetch)"), None);
+        assert_eq!(
+            get_ssh_target("git@github.com:a/b.git (push)"),
+            Some(("git@github.com".into(), None))
+        );
+        assert_eq!(
+            get_ssh_target("ssh://git@git.example.com:2222/a/b.git"),
+            Some(("git@git.example.com".into(), Some("2222".into())))
+        );
     }
 }
@@ -0,0 +1,298 @@
+//! Detection of the project manifests living inside a repository.
+
+use chrono::{DateTime, FixedOffset};
+use git2::{Repository, Sort};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ProjectKind {
+    Cargo,
+    Npm,
+    Go,
+}
+
+/// A project nested inside a monorepo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubProject {
+    pub path: String,
+    pub kind: ProjectKind,
+    /// Latest commit touching this sub-project
+    #[serde(default, deserialize_with = "crate::de_commit_date")]
+    pub latest_commit: Option<DateTime<FixedOffset>>,
+}
+
+/// Directories that never contain projects we care about.
+const SKIP_DIRS: [&str; 4] = ["node_modules", "target", "vendor", "testdata"];
+
+/// Finds the sub-projects of a repo: cargo workspace members, npm workspaces and nested go modules.
+/// Returns an empty Vec for regular single-project repos.
+pub fn sub_projects(repo: &Path) -> Vec<SubProject> {
+    let mut found = Vec::new();
+
+    for member in cargo_workspace_members(repo) {
+        found.push((member, ProjectKind::Cargo));
+    }
+    for member in npm_workspaces(repo) {
+        found.push((member, ProjectKind::Npm));
+    }
+    let mut go_mods = Vec::new();
+    find_nested(repo, "go.mod", 4, &mut go_mods);
+    for dir in go_mods {
+        found.push((dir, ProjectKind::Go));
+    }
+
+    found
+        .into_iter()
+        .filter(|(dir, _)| dir != repo)
+        .map(|(dir, kind)| SubProject {
+            latest_commit: last_touched(repo, &dir),
+            path: dir.to_str().unwrap().to_owned(),
+            kind,
+        })
+        .collect()
+}
+
+fn cargo_workspace_members(repo: &Path) -> Vec<std::path::PathBuf> {
+    let value = match read_toml(&repo.join("Cargo.toml")) {
+        Some(v) => v,
+        None => return Vec::new(),
+    };
+
+    let workspace = match value.get("workspace") {
+        Some(w) => w,
+        None => return Vec::new(),
+    };
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str().map(|s| s.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let excluded = strings("exclude")
+        .iter()
+        .flat_map(|p| expand_glob(repo, p))
+        .collect::<Vec<_>>();
+
+    strings("members")
+        .iter()
+        .flat_map(|p| expand_glob(repo, p))
+        .filter(|p| p.join("Cargo.toml").is_file() && !excluded.contains(p))
+        .collect()
+}
+
+fn npm_workspaces(repo: &Path) -> Vec<std::path::PathBuf> {
+    let value = match fs::read_to_string(repo.join("package.json")) {
+        Ok(s) => match serde_json::from_str::<serde_json::Value>(&s) {
+            Ok(v) => v,
+            Err(_) => return Vec::new(),
+        },
+        Err(_) => return Vec::new(),
+    };
+
+    // "workspaces" is either a list of globs or { "packages": [...] } (yarn)
+    let patterns = match value.get("workspaces") {
+        Some(serde_json::Value::Array(a)) => a.clone(),
+        Some(serde_json::Value::Object(o)) => o
+            .get("packages")
+            .and_then(|p| p.as_array())
+            .cloned()
+            .unwrap_or_default(),
+        _ => return Vec::new(),
+    };
+
+    patterns
+        .iter()
+        .filter_map(|p| p.as_str())
+        .flat_map(|p| expand_glob(repo, p))
+        .filter(|p| p.join("package.json").is_file())
+        .collect()
+}
+
+/// Collects every directory below `dir` (excluding `dir` itself) that contains `file_name`.
+fn find_nested(dir: &Path, file_name: &str, depth: u32, out: &mut Vec<std::path::PathBuf>) {
+    if depth == 0 {
+        return;
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for e in entries.flatten() {
+        let name = e.file_name();
+        let name = name.to_string_lossy();
+        let path = e.path();
+
+        if !path.is_dir() || name.starts_with('.') || SKIP_DIRS.contains(&name.as_ref()) {
+            continue;
+        }
+        if path.join(file_name).is_file() {
+            out.push(path.clone());
+        }
+        find_nested(&path, file_name, depth - 1, out);
+    }
+}
+
+/// Expands workspace globs like `crates/*` or `packages/**`, relative to `root`.
+/// Only `*` wildcards are supported, `**` is treated as a single level.
+fn expand_glob(root: &Path, pattern: &str) -> Vec<std::path::PathBuf> {
+    let mut paths = vec![root.to_path_buf()];
+
+    for component in pattern.trim_start_matches("./").split('/') {
+        if component.is_empty() || component == "." {
+            continue;
+        }
+
+        paths = if component.contains('*') {
+            paths
+                .iter()
+                .filter_map(|p| fs::read_dir(p).ok())
+                .flat_map(|entries| entries.flatten())
+                .filter(|e| {
+                    e.path().is_dir() && wildcard_match(component, &e.file_name().to_string_lossy())
+                })
+                .map(|e| e.path())
+                .collect()
+        } else {
+            paths.iter().map(|p| p.join(component)).collect()
+        };
+    }
+
+    paths.sort();
+    paths
+}
+
+pub fn wildcard_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            if !name.starts_with(prefix) {
+                return false;
+            }
+            let name = &name[prefix.len()..];
+            let rest = rest.trim_start_matches('*');
+            if rest.is_empty() {
+                return true;
+            }
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| wildcard_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// A `[package]` found in some Cargo.toml
+#[derive(Debug)]
+pub struct CargoPackage {
+    pub name: String,
+    pub version: String,
+    pub edition: String,
+    /// Directory containing the Cargo.toml
+    pub path: String,
+}
+
+/// Lists every cargo package inside a repo, including the root one.
+pub fn cargo_packages(repo: &Path) -> Vec<CargoPackage> {
+    let mut dirs = vec![repo.to_path_buf()];
+    find_nested(repo, "Cargo.toml", 6, &mut dirs);
+
+    dirs.iter()
+        .filter_map(|dir| {
+            let value = read_toml(&dir.join("Cargo.toml"))?;
+            let package = value.get("package")?;
+            // fields inherited from the workspace are tables like `{ workspace = true }`
+            let field = |key: &str, default: &str| match package.get(key) {
+                Some(toml::Value::String(s)) => s.clone(),
+                Some(_) => "workspace".to_owned(),
+                None => default.to_owned(),
+            };
+
+            Some(CargoPackage {
+                name: field("name", "?"),
+                version: field("version", "0.0.0"),
+                edition: field("edition", "2015"),
+                path: dir.to_str().unwrap().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Names of the crates overridden in `[patch.*]` or `[replace]` sections of the repo's manifests.
+pub fn cargo_patches(repo: &Path) -> Vec<String> {
+    let mut dirs = vec![repo.to_path_buf()];
+    find_nested(repo, "Cargo.toml", 6, &mut dirs);
+
+    let mut names = Vec::new();
+    for dir in dirs {
+        let value = match read_toml(&dir.join("Cargo.toml")) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if let Some(patch) = value.get("patch").and_then(|p| p.as_table()) {
+            for registry in patch.values().filter_map(|r| r.as_table()) {
+                names.extend(registry.keys().cloned());
+            }
+        }
+        if let Some(replace) = value.get("replace").and_then(|r| r.as_table()) {
+            // keys look like "foo:0.1.0"
+            names.extend(
+                replace
+                    .keys()
+                    .map(|k| k.split(':').next().unwrap().to_owned()),
+            );
+        }
+    }
+
+    names
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    fs::read_to_string(path).ok()?.parse::<toml::Value>().ok()
+}
+
+fn last_touched(repo: &Path, dir: &Path) -> Option<DateTime<FixedOffset>> {
+    let git = Repository::open(repo).ok()?;
+    let rel = dir.strip_prefix(repo).ok()?;
+
+    let mut walk = git.revwalk().ok()?;
+    walk.set_sorting(Sort::TIME).ok()?;
+    walk.push_head().ok()?;
+
+    // the newest commit where the subtree differs from its first parent's
+    for oid in walk.flatten() {
+        let commit = git.find_commit(oid).ok()?;
+        let id = commit.tree().ok()?.get_path(rel).ok().map(|e| e.id());
+        let parent_id = commit
+            .parent(0)
+            .ok()
+            .and_then(|p| p.tree().ok()?.get_path(rel).ok().map(|e| e.id()));
+        if id.is_some() && id != parent_id {
+            return Some(crate::commit_date(&commit));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("pplaces-*", "pplaces-core"));
+        assert!(wildcard_match("*-cli", "pplaces-cli"));
+        assert!(!wildcard_match("pplaces-*", "core"));
+        assert!(!wildcard_match("core", "core2"));
+    }
+}
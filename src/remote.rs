@@ -0,0 +1,300 @@
+//! Remote urls as git accepts them: `https://`, `ssh://`, `git://` and the scp-like
+//! `git@github.com:owner/repo.git`.
+//!
+//! Everything that compares or groups remotes goes through [`RemoteUrl`], so the ssh and https
+//! remotes of a repo are recognized as the same one. [`Rewrites`] applies the `insteadOf` rules of
+//! the git config first, for urls like `gh:owner/repo` that only mean something after them.
+
+use crate::run_git;
+use std::{borrow::Cow, path::Path};
+
+/// A remote on some host, local paths and `file://` urls aren't one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Lower case, scp-like urls are `ssh`
+    pub scheme: String,
+    pub user: Option<String>,
+    /// Lower case, without brackets for IPv6 addresses
+    pub host: String,
+    pub port: Option<u16>,
+    /// Path of the repo on the host without `.git`, like `gbrls/pplaces` or
+    /// `group/subgroup/repo` on GitLab
+    pub path: String,
+}
+
+impl RemoteUrl {
+    /// Parses a url, or a line of `git remote -v` with its ` (fetch)`. None for local paths
+    pub fn parse(url: &str) -> Option<RemoteUrl> {
+        let url = url.split(' ').next()?;
+        if is_local_path(url) {
+            return None;
+        }
+
+        let (scheme, authority, path) = match url.split_once("://") {
+            Some((scheme, rest)) => {
+                let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+                let scheme = match scheme.to_lowercase().as_str() {
+                    "git+ssh" | "ssh+git" => "ssh".to_owned(),
+                    scheme => scheme.to_owned(),
+                };
+                (scheme, authority, path)
+            }
+            // scp-like syntax: git@github.com:user/repo, git reads it as a path when a / comes
+            // before the colon
+            None => match url.split_once(':') {
+                Some((authority, path)) if !authority.contains('/') => {
+                    ("ssh".to_owned(), authority, path)
+                }
+                _ => return None,
+            },
+        };
+        if scheme == "file" {
+            return None;
+        }
+
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, rest)) => (Some(user.to_owned()), rest),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => match port.parse() {
+                Ok(port) => (host, Some(port)),
+                // the colon of an IPv6 address
+                Err(_) => (host_port, None),
+            },
+            None => (host_port, None),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        let path = path.trim_matches('/');
+        let path = path
+            .strip_suffix(".git")
+            .unwrap_or(path)
+            .trim_end_matches('/');
+        if host.is_empty() || path.is_empty() {
+            return None;
+        }
+
+        Some(RemoteUrl {
+            scheme,
+            user,
+            host: host.to_lowercase(),
+            port,
+            path: path.to_owned(),
+        })
+    }
+
+    /// User or top level group, the first part of the path
+    pub fn owner(&self) -> &str {
+        self.path.split('/').next().unwrap_or_default()
+    }
+
+    /// Everything before the repo name: the owner, or `group/subgroup` for GitLab subgroups
+    pub fn namespace(&self) -> &str {
+        self.path
+            .rsplit_once('/')
+            .map_or("", |(namespace, _)| namespace)
+    }
+
+    /// Last part of the path, the repo name
+    pub fn repo(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// `host/path` in lower case, the same whichever protocol the remote uses. GitHub and GitLab
+    /// don't mind the case of repo paths either
+    pub fn key(&self) -> String {
+        format!("{}/{}", self.host, self.path).to_lowercase()
+    }
+
+    /// Page of the repo on the forge, like `https://github.com/gbrls/pplaces`
+    pub fn web_url(&self) -> String {
+        format!("https://{}/{}", self.host, self.path)
+    }
+
+    /// Port git connects to when the url doesn't name one, None for unknown protocols
+    pub fn default_port(&self) -> Option<u16> {
+        match self.scheme.as_str() {
+            "https" => Some(443),
+            "http" => Some(80),
+            "git" => Some(9418),
+            "ssh" => Some(22),
+            _ => None,
+        }
+    }
+}
+
+/// The `url.<base>.insteadOf` rules of the git config, urls starting with a prefix are rewritten
+/// to start with the base instead
+#[derive(Debug, Default, Clone)]
+pub struct Rewrites {
+    /// Prefix and base
+    rules: Vec<(String, String)>,
+}
+
+impl Rewrites {
+    /// The rules git would use from the current directory, none when git can't be run
+    pub fn from_git_config() -> Rewrites {
+        // exits with 1 when nothing matches
+        let out = run_git(
+            Path::new("."),
+            &["config", "--get-regexp", r"^url\..*\.insteadof$"],
+        );
+        Rewrites::parse(&out.unwrap_or_default())
+    }
+
+    /// Reads `git config --get-regexp` output, like `url.git@github.com:.insteadof gh:`
+    fn parse(config: &str) -> Rewrites {
+        let rules = config
+            .lines()
+            .filter_map(|l| {
+                let (key, prefix) = l.split_once(' ')?;
+                let base = key.strip_prefix("url.")?.strip_suffix(".insteadof")?;
+                Some((prefix.to_owned(), base.to_owned()))
+            })
+            .collect();
+        Rewrites { rules }
+    }
+
+    /// `url` the way git sees it: the longest matching prefix is replaced, like git does
+    pub fn apply<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        let rule = self
+            .rules
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len());
+        match rule {
+            Some((prefix, base)) => Cow::Owned(format!("{base}{}", &url[prefix.len()..])),
+            None => Cow::Borrowed(url),
+        }
+    }
+
+    /// [`RemoteUrl::parse`] of the rewritten url
+    pub fn remote(&self, url: &str) -> Option<RemoteUrl> {
+        RemoteUrl::parse(&self.apply(url))
+    }
+}
+
+/// Whether git would read `url` as a path on this machine: absolute or relative paths, and the
+/// Windows `C:\src\repo` or `\\server\share\repo` that would otherwise look scp-like with host `C`
+fn is_local_path(url: &str) -> bool {
+    let drive = match url.as_bytes() {
+        [letter, b':'] => letter.is_ascii_alphabetic(),
+        [letter, b':', sep, ..] => letter.is_ascii_alphabetic() && matches!(sep, b'\\' | b'/'),
+        _ => false,
+    };
+    drive || url.starts_with(['/', '.', '\\'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parsed = |url| {
+            let r = RemoteUrl::parse(url).unwrap();
+            (r.scheme, r.user, r.host, r.port, r.path)
+        };
+        assert_eq!(
+            parsed("https://github.com/linebender/runebender (fetch)"),
+            (
+                "https".into(),
+                None,
+                "github.com".into(),
+                None,
+                "linebender/runebender".into()
+            )
+        );
+        assert_eq!(
+            parsed("git@github.com:gbrls/Bootloader.git (fetch)"),
+            (
+                "ssh".into(),
+                Some("git".into()),
+                "github.com".into(),
+                None,
+                "gbrls/Bootloader".into()
+            )
+        );
+        assert_eq!(
+            parsed("ssh://git@git.example.com:2222/team/repo.git/"),
+            (
+                "ssh".into(),
+                Some("git".into()),
+                "git.example.com".into(),
+                Some(2222),
+                "team/repo".into()
+            )
+        );
+        assert_eq!(
+            parsed("git://[::1]:9419/a/b"),
+            ("git".into(), None, "::1".into(), Some(9419), "a/b".into())
+        );
+    }
+
+    #[test]
+    fn test_local_paths() {
+        for url in [
+            "/srv/git/repo.git",
+            "../repo",
+            "file:///srv/git/repo.git",
+            "./dir:with/colon",
+            r"C:\src\repo (fetch)",
+            "D:/src/repo",
+            r"\\nas\git\repo.git",
+        ] {
+            assert_eq!(RemoteUrl::parse(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_subgroups() {
+        let r = RemoteUrl::parse("git@gitlab.com:group/sub/repo.git").unwrap();
+        assert_eq!(r.owner(), "group");
+        assert_eq!(r.namespace(), "group/sub");
+        assert_eq!(r.repo(), "repo");
+        assert_eq!(r.key(), "gitlab.com/group/sub/repo");
+    }
+
+    #[test]
+    fn test_rewrites() {
+        let rewrites = Rewrites::parse(
+            "url.git@github.com:.insteadof https://github.com/\n\
+             url.git@github.com:.insteadof gh:\n\
+             url.git@github-work:acme/.insteadof https://github.com/acme/\n",
+        );
+        assert_eq!(
+            rewrites.apply("gh:gbrls/pplaces"),
+            "git@github.com:gbrls/pplaces"
+        );
+        assert_eq!(
+            rewrites.apply("https://github.com/gbrls/pplaces"),
+            "git@github.com:gbrls/pplaces"
+        );
+        assert_eq!(
+            rewrites.apply("https://github.com/acme/api"),
+            "git@github-work:acme/api"
+        );
+        assert_eq!(
+            rewrites.apply("https://gitlab.com/a/b"),
+            "https://gitlab.com/a/b"
+        );
+        assert_eq!(
+            rewrites.remote("gh:gbrls/pplaces").map(|r| r.key()),
+            Some("github.com/gbrls/pplaces".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_same_key() {
+        let key = |url| RemoteUrl::parse(url).unwrap().key();
+        assert_eq!(
+            key("git@github.com:gbrls/pplaces.git"),
+            key("https://github.com/Gbrls/pplaces")
+        );
+        assert_eq!(
+            key("ssh://git@github.com/gbrls/pplaces"),
+            key("https://github.com/gbrls/pplaces.git")
+        );
+    }
+}
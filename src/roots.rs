@@ -0,0 +1,112 @@
+//! Scan roots remembered with `pplaces root add` or by scanning a path.
+//!
+//! They are kept one per line in `roots` inside the config dir. The `roots` of config.toml always
+//! come first and can't be removed from here.
+
+use crate::{canonical_path, config::Config, config_dir};
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+fn registry() -> Result<PathBuf> {
+    Ok(config_dir().context("No config directory")?.join("roots"))
+}
+
+/// The registered roots, a missing registry is an empty one
+pub fn load() -> Result<Vec<PathBuf>> {
+    let path = registry()?;
+    match fs::read_to_string(&path) {
+        Ok(text) => Ok(parse(&text)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| format!("Could not read {path:?}")),
+    }
+}
+
+fn parse(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn save(roots: &[PathBuf]) -> Result<()> {
+    let path = registry()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text = roots
+        .iter()
+        .map(|r| format!("{}\n", r.display()))
+        .collect::<String>();
+    fs::write(&path, text).with_context(|| format!("Could not write {path:?}"))
+}
+
+/// Registers `paths`, returning the ones that weren't known yet
+pub fn add(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut roots = load()?;
+    let mut added = Vec::new();
+    for path in paths {
+        let path = canonical_path(path).with_context(|| format!("{path:?} does not exist"))?;
+        if !roots.contains(&path) {
+            roots.push(path.clone());
+            added.push(path);
+        }
+    }
+    if !added.is_empty() {
+        save(&roots)?;
+    }
+
+    Ok(added)
+}
+
+/// Forgets `path`, false when it wasn't registered
+pub fn remove(path: &Path) -> Result<bool> {
+    // the directory may be gone already
+    let path = canonical_path(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut roots = load()?;
+    let before = roots.len();
+    roots.retain(|r| *r != path);
+    if roots.len() == before {
+        return Ok(false);
+    }
+    save(&roots)?;
+
+    Ok(true)
+}
+
+/// What a bare `scan` looks at: the config roots followed by the registered ones
+pub fn all(config: &Config) -> Result<Vec<PathBuf>> {
+    Ok(merge(config.roots(), load()?))
+}
+
+fn merge(mut roots: Vec<PathBuf>, registered: Vec<PathBuf>) -> Vec<PathBuf> {
+    for root in registered {
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let config = vec![PathBuf::from("/src")];
+        let registered = parse("/work\n\n/src\n  /notes  \n");
+        assert_eq!(
+            merge(config, registered),
+            vec![
+                PathBuf::from("/src"),
+                PathBuf::from("/work"),
+                PathBuf::from("/notes")
+            ]
+        );
+    }
+}
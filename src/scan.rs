@@ -0,0 +1,697 @@
+//! Finding the repos below a directory and reading what the cache keeps about each of them.
+
+use crate::{
+    authors, canonical_path, commit_date, default_jobs, inventory, manifest, status, upsert_repo,
+    Cache, ProjectMetadata,
+};
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Mutex,
+    },
+    thread,
+};
+use tracing::{debug, info, warn};
+
+/// How `scan` walks the directories
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Args)]
+pub struct ScanOpts {
+    /// How many directories/repos to process at the same time
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+
+    /// Read every repo again, even those whose HEAD didn't move since the last scan
+    #[clap(long)]
+    pub force: bool,
+
+    /// Also count the commits of each author, including your own, which walks the whole history
+    /// of every repo. Implies --force
+    #[clap(long)]
+    pub authors: bool,
+
+    /// Keep submodule checkouts as repos of their own instead of only listing them under the
+    /// repo that uses them
+    #[clap(long)]
+    pub include_submodules: bool,
+
+    /// Descend into symlinked directories, every directory is still only walked once
+    #[clap(long)]
+    pub follow_symlinks: bool,
+
+    /// Don't descend more than this many directories below the scan root
+    #[clap(long)]
+    pub max_depth: Option<usize>,
+
+    /// Stay on the filesystem of the scan root, skipping mount points below it
+    #[clap(long)]
+    pub one_file_system: bool,
+
+    /// Skip directories matching this, on top of the config ones. A pattern with a `/` is
+    /// matched against the whole path, e.g. `/mnt/*`, otherwise against the directory name
+    #[clap(long)]
+    pub exclude: Vec<String>,
+
+    /// Don't read the size of the repos, which goes through every file in them
+    #[clap(long)]
+    pub no_sizes: bool,
+
+    /// Don't read uncommitted changes, stashes and the commits not pushed or pulled yet
+    #[clap(long)]
+    pub no_status: bool,
+}
+
+/// Walks `path` looking for repos and fetches their metadata as they are found.
+/// A pool of `opts.jobs` threads walks the directories and hands the repos it finds to as many
+/// reader threads through a bounded channel, so the walk goes on while repos are read and doesn't
+/// run far ahead of them.
+/// Directories matching one of `opts.exclude` are skipped, see [`is_excluded`].
+///
+/// Repos in `known` whose branch, HEAD commit and last fetch are still the ones recorded there
+/// only get their status read again, see [`refresh_unchanged`].
+///
+/// Directories that can't be read and repos that can't be opened don't stop the walk, they are
+/// logged and returned with the other skipped directories.
+fn scan(
+    path: &Path,
+    opts: &ScanOpts,
+    known: &Known,
+    progress: &ScanProgress,
+) -> Result<(Vec<ProjectMetadata>, ScanSkips)> {
+    // without readers the walk would wait on the queue forever
+    let jobs = opts.jobs.unwrap_or_else(default_jobs).max(1);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("Could not start the scan threads")?;
+
+    let (repos, queue) = mpsc::sync_channel(jobs * 4);
+    let queue = Mutex::new(queue);
+    let found = Mutex::new(Vec::new());
+    let errors = Mutex::new(Vec::new());
+    let walk = Walk {
+        opts,
+        progress,
+        repos,
+        device: opts.one_file_system.then(|| device_of(path)).flatten(),
+        visited: Mutex::new(HashSet::from([path.to_path_buf()])),
+        errors: &errors,
+        too_deep: AtomicUsize::new(0),
+        other_fs: AtomicUsize::new(0),
+    };
+
+    let (too_deep, other_fs) = thread::scope(|s| {
+        for _ in 0..jobs {
+            s.spawn(|| read_found(&queue, known, opts, &found, &errors));
+        }
+        pool.scope(|s| scan_dir(s, path.to_path_buf(), 0, &walk));
+        // the readers stop once the queue is empty and there is no sender left
+        drop(walk.repos);
+        (walk.too_deep.into_inner(), walk.other_fs.into_inner())
+    });
+
+    let skips = ScanSkips {
+        errors: errors.into_inner().unwrap(),
+        too_deep,
+        other_fs,
+    };
+    Ok((found.into_inner().unwrap(), skips))
+}
+
+/// Counters a scan updates as it goes, to show how far along it is while it runs
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    /// Directories read so far
+    pub dirs: AtomicUsize,
+    /// Repos found so far, the unchanged ones included
+    pub repos: AtomicUsize,
+    /// One of the directories being read right now
+    pub current: Mutex<PathBuf>,
+}
+
+/// What a scan couldn't or wasn't allowed to look at
+#[derive(Debug, Default)]
+pub struct ScanSkips {
+    /// Directories that couldn't be read and repos that couldn't be opened
+    pub errors: Vec<String>,
+    /// Directories below `--max-depth`
+    pub too_deep: usize,
+    /// Mount points left out by `--one-file-system`
+    pub other_fs: usize,
+}
+
+impl ScanSkips {
+    pub fn add(&mut self, other: ScanSkips) {
+        self.errors.extend(other.errors);
+        self.too_deep += other.too_deep;
+        self.other_fs += other.other_fs;
+    }
+}
+
+/// Work tree and git dir of a repo found by the walk, the git dir when it isn't `.git` inside
+type Found = (PathBuf, Option<PathBuf>);
+
+/// State shared by the threads walking the directories
+struct Walk<'a> {
+    opts: &'a ScanOpts,
+    progress: &'a ScanProgress,
+    /// Repos found, for the reader threads
+    repos: SyncSender<Found>,
+    /// Device of the scan root with `--one-file-system`
+    device: Option<u64>,
+    /// Canonical paths of the directories walked so far, only kept when following symlinks
+    visited: Mutex<HashSet<PathBuf>>,
+    errors: &'a Mutex<Vec<String>>,
+    too_deep: AtomicUsize,
+    other_fs: AtomicUsize,
+}
+
+impl Walk<'_> {
+    fn skip(&self, err: String) {
+        skip(self.errors, err);
+    }
+
+    /// Queues the repo for the readers, waiting while they are behind
+    fn found(&self, work_tree: PathBuf, git_dir: Option<PathBuf>) {
+        self.progress.repos.fetch_add(1, Ordering::Relaxed);
+        // only fails once the readers are gone, which they aren't before the walk is done
+        let _ = self.repos.send((work_tree, git_dir));
+    }
+}
+
+fn skip(errors: &Mutex<Vec<String>>, err: String) {
+    warn!("Skipping {err}");
+    errors.lock().unwrap().push(err);
+}
+
+/// `depth` counts the directories between `path` and the scan root
+fn scan_dir<'a>(s: &rayon::Scope<'a>, path: PathBuf, depth: usize, walk: &'a Walk<'a>) {
+    walk.progress.dirs.fetch_add(1, Ordering::Relaxed);
+    // whichever thread gets there, the others don't wait for it
+    if let Ok(mut current) = walk.progress.current.try_lock() {
+        current.clone_from(&path);
+    }
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(err) => return walk.skip(format!("{}: {err}", path.display())),
+    };
+    for e in entries {
+        let e = match e {
+            Ok(e) => e,
+            Err(err) => {
+                walk.skip(format!("an entry of {}: {err}", path.display()));
+                continue;
+            }
+        };
+        let mut entry = e.path();
+        if is_excluded(&walk.opts.exclude, &entry) {
+            debug!("Skipping {}, it is excluded", entry.display());
+            continue;
+        }
+        let is_link = e.file_type().map(|t| t.is_symlink()).unwrap_or(false);
+        if is_link && !walk.opts.follow_symlinks {
+            debug!("Skipping {}, it is a symlink", entry.display());
+            continue;
+        }
+
+        if entry.ends_with(".git") {
+            // a file for worktrees and submodules
+            walk.found(path.clone(), None);
+            continue;
+        }
+        if !entry.is_dir() {
+            continue;
+        }
+        if walk.opts.max_depth.is_some_and(|max| depth + 1 > max) {
+            debug!("Skipping {}, it is below --max-depth", entry.display());
+            walk.too_deep.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if walk.device.is_some() && device_of(&entry) != walk.device {
+            debug!("Skipping {}, it is on another filesystem", entry.display());
+            walk.other_fs.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        if walk.opts.follow_symlinks {
+            // the same directory can be reachable through several links, or a link to a parent
+            entry = match canonical_path(&entry) {
+                Ok(entry) => entry,
+                Err(err) => {
+                    walk.skip(format!("{}: {err}", entry.display()));
+                    continue;
+                }
+            };
+            if !walk.visited.lock().unwrap().insert(entry.clone()) {
+                continue;
+            }
+        }
+        if is_bare_repo(&entry) {
+            walk.found(entry.clone(), Some(entry));
+        } else {
+            s.spawn(move |s| scan_dir(s, entry, depth + 1, walk));
+        }
+    }
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Reads the repos of `queue` until the walk is done, only the status of those whose HEAD didn't
+/// move since the last scan
+fn read_found(
+    queue: &Mutex<Receiver<Found>>,
+    known: &Known,
+    opts: &ScanOpts,
+    found: &Mutex<Vec<ProjectMetadata>>,
+    errors: &Mutex<Vec<String>>,
+) {
+    loop {
+        // the lock is held while waiting for a repo, not while reading it
+        let next = queue.lock().unwrap().recv();
+        let (work_tree, git_dir) = match next {
+            Ok(repo) => repo,
+            Err(_) => return,
+        };
+        if let Some(data) = refresh_unchanged(&work_tree, git_dir.as_deref(), known, opts) {
+            debug!("{} didn't move since the last scan", work_tree.display());
+            found.lock().unwrap().push(data);
+            continue;
+        }
+        debug!("Reading {}", work_tree.display());
+        match fetch_scanned(&work_tree, git_dir.as_deref(), opts) {
+            Ok(mut data) => {
+                if opts.authors {
+                    read_authors(&mut data, git_dir.as_deref());
+                }
+                found.lock().unwrap().push(data)
+            }
+            Err(err) => skip(errors, format!("{}: {err:#}", work_tree.display())),
+        }
+    }
+}
+
+/// Fills in the counts of `scan --authors`, a repo whose history can't be walked gets none
+fn read_authors(data: &mut ProjectMetadata, git_dir: Option<&Path>) {
+    let counted =
+        status::open(Path::new(&data.path), git_dir).and_then(|repo| authors::count(&repo));
+    match counted {
+        Ok((top, mine)) => {
+            data.top_authors = top;
+            data.my_commits = Some(mine);
+        }
+        Err(err) => debug!("Could not count the authors of {}: {err:#}", data.path),
+    }
+}
+
+/// HEAD, objects and refs at the top, like `git clone --bare` leaves them. `.git` dirs look the
+/// same but are handled with their work tree.
+pub fn is_bare_repo(path: &Path) -> bool {
+    !path.ends_with(".git")
+        && path.join("HEAD").is_file()
+        && path.join("objects").is_dir()
+        && path.join("refs").is_dir()
+}
+
+/// Patterns with a `/` match the whole path, the others only the last component, `*` matches
+/// anything in both. On Windows `\` works like `/` in patterns and paths
+pub fn is_excluded(exclude: &[String], path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let full = path.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+    exclude.iter().any(|p| {
+        let p = p.replace(MAIN_SEPARATOR, "/");
+        if p.contains('/') {
+            manifest::wildcard_match(p.trim_end_matches('/'), &full)
+        } else {
+            manifest::wildcard_match(&p, &name)
+        }
+    })
+}
+
+/// Cached entries by path
+type Known = HashMap<String, ProjectMetadata>;
+
+/// The cached entry with its status read again when the checked out branch, HEAD commit and last
+/// fetch are still the recorded ones. None when the repo has to be read again in full: it moved,
+/// fetched (tags and the fork parent may have too) or isn't known
+fn refresh_unchanged(
+    path: &Path,
+    git_dir: Option<&Path>,
+    known: &Known,
+    opts: &ScanOpts,
+) -> Option<ProjectMetadata> {
+    let old = known.get(path.to_str()?)?;
+    // registered repos aren't found with their git dir by the walk
+    let git_dir = git_dir.or(old.git_dir.as_deref().map(Path::new));
+    let mut repo = status::open(path, git_dir).ok()?;
+    let moved = status::current_branch(&repo) != old.current_branch
+        || status::head_commit(&repo) != old.head_commit
+        || status::last_fetch(&repo) != old.last_fetch;
+    if moved {
+        return None;
+    }
+
+    let mut data = old.clone();
+    read_status(&mut data, &mut repo, path, git_dir, opts);
+    Some(data)
+}
+
+/// Fills in what changes without HEAD moving: uncommitted changes, stashes, commits not pushed
+/// or not pulled yet and the size. What `opts` leaves out keeps the value it had
+fn read_status(
+    data: &mut ProjectMetadata,
+    repo: &mut git2::Repository,
+    path: &Path,
+    git_dir: Option<&Path>,
+    opts: &ScanOpts,
+) {
+    if !opts.no_sizes {
+        data.size_bytes = inventory::dir_size(path)
+            + git_dir
+                .filter(|d| *d != path)
+                .map(inventory::dir_size)
+                .unwrap_or_default();
+    }
+    if opts.no_status {
+        return;
+    }
+
+    let (modified, untracked) = status::worktree_changes(repo).unwrap_or_default();
+    data.dirty = modified + untracked > 0;
+    data.modified = modified;
+    data.untracked = untracked;
+
+    let ahead_behind = status::ahead_behind(repo).ok().flatten();
+    data.ahead = ahead_behind.map(|(a, _)| a);
+    data.behind = ahead_behind.map(|(_, b)| b);
+    let parent_ahead_behind = data
+        .fork_of
+        .as_ref()
+        .and_then(|_| status::ahead_behind_parent(repo));
+    data.parent_ahead = parent_ahead_behind.map(|(a, _)| a);
+    data.parent_behind = parent_ahead_behind.map(|(_, b)| b);
+    data.unpushed = status::unpushed(repo).unwrap_or_default();
+    data.stashes = status::stash_count(repo).unwrap_or_default();
+}
+
+/// Linked work trees are listed under their main repo, entries of their own are dropped
+fn drop_worktrees(cache: &mut Cache) {
+    let worktrees = cache
+        .iter()
+        .flat_map(|e| e.worktrees.iter().map(|w| w.path.clone()))
+        .collect::<Vec<_>>();
+    cache.retain(|e| !worktrees.contains(&e.path));
+}
+
+/// Entries reached through symlinks before point at the same repo as the canonical path found
+/// now, the latest entry for a repo wins
+fn dedupe_canonical(cache: &mut Cache) {
+    let mut seen = HashSet::new();
+    let mut keep = cache
+        .iter()
+        .rev()
+        .map(|e| {
+            let path = Path::new(&e.path);
+            seen.insert(canonical_path(path).unwrap_or_else(|_| path.to_path_buf()))
+        })
+        .collect::<Vec<_>>();
+    keep.reverse();
+    let mut keep = keep.into_iter();
+    cache.retain(|_| keep.next().unwrap_or(true));
+}
+
+/// Submodule checkouts are listed under the repo using them, entries of their own are dropped
+fn drop_submodules(cache: &mut Cache) {
+    let submodules = cache
+        .iter()
+        .flat_map(|e| e.submodules.clone())
+        .collect::<Vec<_>>();
+    cache.retain(|e| !submodules.contains(&e.path));
+}
+
+/// `git_dir` is only needed when it isn't `path/.git`, for bare repos it is `path` itself
+pub fn fetch_metadata(path: &Path, git_dir: Option<&Path>) -> Result<ProjectMetadata> {
+    fetch_scanned(path, git_dir, &ScanOpts::default())
+}
+
+/// [`fetch_metadata`] without the size or the status when `opts` leaves them out, they are zero
+pub fn fetch_scanned(
+    path: &Path,
+    git_dir: Option<&Path>,
+    opts: &ScanOpts,
+) -> Result<ProjectMetadata> {
+    let mut repo = status::open(path, git_dir).context("Could not open the repo")?;
+
+    // same shape as `git remote -v`: "url (fetch)" and "url (push)" per remote
+    let mut upstreams = Vec::new();
+    for name in repo.remotes()?.iter().flatten() {
+        let remote = match repo.find_remote(name) {
+            Ok(remote) => remote,
+            Err(_) => continue,
+        };
+        if let Some(url) = remote.url() {
+            upstreams.push(format!("{url} (fetch)"));
+            upstreams.push(format!("{} (push)", remote.pushurl().unwrap_or(url)));
+        }
+    }
+
+    let (date, summary, author) = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => (
+            Some(commit_date(&commit)),
+            commit.summary().map(|s| s.to_owned()),
+            commit.author().name().map(|n| n.to_owned()),
+        ),
+        Err(_) => (None, None, None),
+    };
+
+    let latest_tag = status::latest_tag(&repo);
+
+    let mut data = ProjectMetadata {
+        path: path.to_str().context("Path is not valid UTF-8")?.to_owned(),
+        latest_commit: date,
+        last_commit_summary: summary,
+        last_commit_author: author,
+        upstream: upstreams,
+        has_docker: detect_docker(path, 2),
+        projects: manifest::sub_projects(path),
+        worktrees: status::worktrees(&repo).unwrap_or_default(),
+        submodules: submodule_paths(&repo, path),
+        git_dir: git_dir
+            .map(|d| d.to_str().context("Git dir path is not valid UTF-8"))
+            .transpose()?
+            .map(|d| d.to_owned()),
+        fork_of: status::fork_parent(&repo),
+        last_fetch: status::last_fetch(&repo),
+        commits_since_tag: latest_tag.as_ref().map_or(0, |(_, since)| *since),
+        latest_tag: latest_tag.map(|(tag, _)| tag),
+        current_branch: status::current_branch(&repo),
+        default_branch: status::default_branch(&repo),
+        language: tracked_language(&repo),
+        bare: repo.is_bare(),
+        head_commit: status::head_commit(&repo),
+        ..Default::default()
+    };
+    read_status(&mut data, &mut repo, path, git_dir, opts);
+    Ok(data)
+}
+
+/// Where the submodules of `.gitmodules` are checked out, whether they are or not
+fn submodule_paths(repo: &git2::Repository, path: &Path) -> Vec<String> {
+    let submodules = match repo.submodules() {
+        Ok(submodules) => submodules,
+        Err(_) => return Vec::new(),
+    };
+    submodules
+        .iter()
+        .map(|s| path.join(s.path()).to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Dominant language of the files in the index
+fn tracked_language(repo: &git2::Repository) -> Option<String> {
+    let index = repo.index().ok()?;
+    let paths = index
+        .iter()
+        .map(|e| String::from_utf8_lossy(&e.path).into_owned())
+        .collect::<Vec<_>>();
+    inventory::language_of_files(paths.iter().map(|p| p.as_str()))
+}
+
+/// Looks for container build files or kubernetes manifests up to `depth` levels deep.
+fn detect_docker(path: &Path, depth: u32) -> bool {
+    const FILES: [&str; 7] = [
+        "Dockerfile",
+        "Containerfile",
+        "docker-compose.yml",
+        "docker-compose.yaml",
+        "compose.yml",
+        "compose.yaml",
+        "kustomization.yaml",
+    ];
+    const DIRS: [&str; 3] = ["k8s", "kubernetes", "helm"];
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    for e in entries.flatten() {
+        let name = e.file_name();
+        let name = name.to_string_lossy();
+
+        if e.path().is_dir() {
+            if DIRS.contains(&name.as_ref()) {
+                return true;
+            }
+            // don't descend into git internals or dependency trees
+            let skip = name.starts_with('.') || name == "node_modules" || name == "target";
+            if depth > 0 && !skip && detect_docker(&e.path(), depth - 1) {
+                return true;
+            }
+        } else if FILES.contains(&name.as_ref()) || name.starts_with("Dockerfile.") {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Adds the repos found under `paths` to `data`, returning the cache and what wasn't scanned.
+/// Without `force` only repos whose HEAD moved are read again in full, the others only get their
+/// status and size updated.
+pub fn build_cache(data: Cache, paths: &[PathBuf], opts: &ScanOpts) -> Result<(Cache, ScanSkips)> {
+    build_cache_with_progress(data, paths, opts, &ScanProgress::default())
+}
+
+/// [`build_cache`], counting what it went through in `progress` for another thread to show
+pub fn build_cache_with_progress(
+    mut data: Cache,
+    paths: &[PathBuf],
+    opts: &ScanOpts,
+    progress: &ScanProgress,
+) -> Result<(Cache, ScanSkips)> {
+    let known = if opts.force || opts.authors {
+        Known::new()
+    } else {
+        data.iter().map(|e| (e.path.clone(), e.clone())).collect()
+    };
+
+    let mut skips = ScanSkips::default();
+    for path in paths {
+        info!("Scanning {}", path.display());
+        let (found, skipped) = scan(path, opts, &known, progress)?;
+        for repo in found {
+            upsert_repo(repo, &mut data);
+        }
+        skips.add(skipped);
+    }
+    drop_worktrees(&mut data);
+    dedupe_canonical(&mut data);
+    if !opts.include_submodules {
+        drop_submodules(&mut data);
+    }
+    data.sort_by_key(|d| d.latest_commit);
+    data.reverse();
+
+    Ok((data, skips))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() {
+        let exclude = [
+            "target".to_owned(),
+            "/mnt/*".to_owned(),
+            "/src/vendor/".to_owned(),
+        ];
+        assert!(is_excluded(&exclude, Path::new("/src/pplaces/target")));
+        assert!(is_excluded(&exclude, Path::new("/mnt/nas")));
+        assert!(is_excluded(&exclude, Path::new("/src/vendor")));
+        assert!(!is_excluded(&exclude, Path::new("/src/target-dir")));
+        assert!(!is_excluded(&exclude, Path::new("/src/mnt/nas")));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_is_excluded_windows() {
+        let exclude = [r"C:\mnt\*".to_owned(), "D:/vendor/".to_owned()];
+        assert!(is_excluded(&exclude, Path::new(r"C:\mnt\nas")));
+        assert!(is_excluded(&exclude, Path::new(r"D:\vendor")));
+        assert!(!is_excluded(&exclude, Path::new(r"C:\src\mnt")));
+    }
+
+    #[test]
+    fn test_refresh_unchanged() {
+        let dir = std::env::temp_dir().join(format!("pplaces-scan-{}", std::process::id()));
+        let repo = git2::Repository::init(&dir).unwrap();
+        let sig = git2::Signature::now("Someone", "someone@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let first = repo
+            .commit(Some("HEAD"), &sig, &sig, "Start", &tree, &[])
+            .unwrap();
+
+        let old = fetch_metadata(&dir, None).unwrap();
+        assert!(!old.dirty);
+        let known = Known::from([(old.path.clone(), old)]);
+
+        fs::write(dir.join("notes.txt"), "todo").unwrap();
+        let data = refresh_unchanged(&dir, None, &known, &ScanOpts::default()).unwrap();
+        assert!(data.dirty);
+        assert_eq!(data.untracked, 1);
+        let opts = ScanOpts {
+            no_status: true,
+            ..Default::default()
+        };
+        assert!(!refresh_unchanged(&dir, None, &known, &opts).unwrap().dirty);
+
+        let first = repo.find_commit(first).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "More", &tree, &[&first])
+            .unwrap();
+        assert!(refresh_unchanged(&dir, None, &known, &ScanOpts::default()).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_drop_worktrees() {
+        let mut data = vec![
+            ProjectMetadata {
+                path: "/src/a".to_owned(),
+                worktrees: vec![status::Worktree {
+                    path: "/src/a-fix".to_owned(),
+                    branch: Some("fix".to_owned()),
+                }],
+                ..Default::default()
+            },
+            ProjectMetadata {
+                path: "/src/a-fix".to_owned(),
+                ..Default::default()
+            },
+            ProjectMetadata {
+                path: "/src/b".to_owned(),
+                ..Default::default()
+            },
+        ];
+        drop_worktrees(&mut data);
+        let paths = data.iter().map(|e| e.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, ["/src/a", "/src/b"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Activity across the whole cache for `stats`: commits per week and month over the last year,
+//! the most active repos of the last month and totals.
+
+use crate::{human_size, inventory, parallel_map, run_git, Cache};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate};
+use clap::ArgEnum;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const WEEKS: i64 = 12;
+const MONTHS: u32 = 12;
+const MOST_ACTIVE: usize = 10;
+/// Upper bound of the commits read from each repo
+const MAX_COMMITS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, ArgEnum)]
+pub enum StatsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+pub struct Stats {
+    /// RFC 3339
+    pub generated_at: String,
+    pub repos: usize,
+    pub size_bytes: u64,
+    /// Repos with uncommitted changes when they were last scanned
+    pub dirty: usize,
+    /// Oldest first, each starting on a monday
+    pub weeks: Vec<Period>,
+    /// Oldest first, like 2024-06
+    pub months: Vec<Period>,
+    /// Commits in the last 30 days, most first
+    pub most_active: Vec<RepoActivity>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Period {
+    pub start: String,
+    pub commits: usize,
+}
+
+#[derive(Serialize)]
+pub struct RepoActivity {
+    pub path: String,
+    pub commits: usize,
+}
+
+/// Local dates of the commits on any branch of the repo since `since`
+pub fn commit_dates(path: &Path, since: DateTime<Local>) -> Result<Vec<NaiveDate>> {
+    let out = run_git(
+        path,
+        &[
+            "log",
+            "--all",
+            &format!("--since={}", since.to_rfc3339()),
+            &format!("--max-count={MAX_COMMITS}"),
+            "--format=%aI",
+        ],
+    )?;
+    Ok(out
+        .lines()
+        .filter_map(|l| DateTime::parse_from_rfc3339(l).ok())
+        .map(|d| d.with_timezone(&Local).naive_local().date())
+        .collect())
+}
+
+pub fn collect(data: &Cache, jobs: usize) -> Stats {
+    let now = Local::now();
+    let today = now.naive_local().date();
+    let year_ago = now - Duration::days(365);
+    let month_ago = today - Duration::days(30);
+
+    let per_repo = parallel_map(data, jobs, |e| {
+        let path = Path::new(&e.path);
+        // repos without commits have no log
+        let dates = commit_dates(path, year_ago).unwrap_or_default();
+        (dates, inventory::dir_size(path))
+    });
+
+    let mut all = Vec::new();
+    let mut most_active = Vec::new();
+    for (e, (dates, _)) in data.iter().zip(&per_repo) {
+        let recent = dates.iter().filter(|d| **d > month_ago).count();
+        if recent > 0 {
+            most_active.push(RepoActivity {
+                path: e.path.clone(),
+                commits: recent,
+            });
+        }
+        all.extend(dates);
+    }
+    most_active.sort_by_key(|a| std::cmp::Reverse(a.commits));
+    most_active.truncate(MOST_ACTIVE);
+
+    let (weeks, months) = bucket(&all, today);
+    Stats {
+        generated_at: now.to_rfc3339(),
+        repos: data.len(),
+        size_bytes: per_repo.iter().map(|(_, size)| size).sum(),
+        dirty: data.iter().filter(|e| e.dirty).count(),
+        weeks,
+        months,
+        most_active,
+    }
+}
+
+/// Commits per week for the last `WEEKS` weeks and per month for the last `MONTHS` months,
+/// the current ones included
+fn bucket(dates: &[NaiveDate], today: NaiveDate) -> (Vec<Period>, Vec<Period>) {
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let weeks = (0..WEEKS)
+        .rev()
+        .map(|i| {
+            let start = monday - Duration::weeks(i);
+            let end = start + Duration::weeks(1);
+            Period {
+                start: start.to_string(),
+                commits: dates.iter().filter(|d| **d >= start && **d < end).count(),
+            }
+        })
+        .collect();
+
+    let months = (0..MONTHS)
+        .rev()
+        .map(|i| {
+            // months since year 0, to step back over year boundaries
+            let n = today.year() * 12 + today.month0() as i32 - i as i32;
+            let (year, month) = (n.div_euclid(12), n.rem_euclid(12) as u32 + 1);
+            Period {
+                start: format!("{year}-{month:02}"),
+                commits: dates
+                    .iter()
+                    .filter(|d| d.year() == year && d.month() == month)
+                    .count(),
+            }
+        })
+        .collect();
+
+    (weeks, months)
+}
+
+pub fn print(stats: &Stats, format: StatsFormat) -> Result<()> {
+    match format {
+        StatsFormat::Json => println!("{}", serde_json::to_string_pretty(stats)?),
+        StatsFormat::Text => {
+            println!(
+                "{} repos, {}, {} with uncommitted changes",
+                stats.repos,
+                human_size(stats.size_bytes),
+                stats.dirty
+            );
+
+            println!("\nCommits per week");
+            print_bars(&stats.weeks);
+            println!("\nCommits per month");
+            print_bars(&stats.months);
+
+            println!("\nMost active in the last 30 days");
+            for r in &stats.most_active {
+                println!("{:6}  {}", r.commits, r.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_bars(periods: &[Period]) {
+    const WIDTH: usize = 40;
+    let max = periods.iter().map(|p| p.commits).max().unwrap_or_default();
+    for p in periods {
+        let bar = if max == 0 {
+            0
+        } else {
+            (p.commits * WIDTH).div_ceil(max)
+        };
+        println!("{:10}  {:6}  {}", p.start, p.commits, "█".repeat(bar));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        // a wednesday
+        let today = date("2024-01-10");
+        let dates = [
+            date("2024-01-08"),
+            date("2024-01-10"),
+            date("2024-01-07"),
+            date("2023-12-20"),
+            date("2023-02-15"),
+        ];
+
+        let (weeks, months) = bucket(&dates, today);
+        assert_eq!(weeks.len(), 12);
+        assert_eq!(
+            weeks.last(),
+            Some(&Period {
+                start: "2024-01-08".to_owned(),
+                commits: 2
+            })
+        );
+        assert_eq!(weeks[10].commits, 1);
+        assert_eq!(weeks[8].commits, 1);
+
+        assert_eq!(months.len(), 12);
+        assert_eq!(months[0].start, "2023-02");
+        assert_eq!(months[0].commits, 1);
+        assert_eq!(months[10].commits, 1);
+        assert_eq!(months[11].commits, 3);
+    }
+}
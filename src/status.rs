@@ -0,0 +1,304 @@
+//! Working state of a repo: uncommitted changes, commits not shared with the upstream and stashes.
+//!
+//! libgit2 only rehashes files whose stat data differs from the index, which is quick for most
+//! repos. Big ones set up with `core.fsmonitor` or `core.untrackedCache` are asked through git
+//! itself, which knows which files were touched without looking at every one of them.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use git2::{BranchType, DescribeFormatOptions, DescribeOptions, Repository, StatusOptions};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path, process::Command};
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepoStatus {
+    /// Files with uncommitted changes, untracked ones included
+    pub changed: usize,
+    /// Commits ahead of and behind the upstream of the checked out branch, None without one
+    pub ahead_behind: Option<(usize, usize)>,
+    pub stashes: usize,
+}
+
+impl RepoStatus {
+    /// Nothing that would be lost by deleting the clone
+    pub fn is_clean(&self) -> bool {
+        self.changed == 0 && self.stashes == 0 && self.ahead_behind.is_none_or(|(a, _)| a == 0)
+    }
+}
+
+/// A linked work tree, made with `git worktree add`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Worktree {
+    pub path: String,
+    /// None when HEAD is detached
+    pub branch: Option<String>,
+}
+
+/// Opens the repo at `path`, whose git dir is `git_dir` when it isn't `path/.git`.
+/// A bare repo is its own git dir.
+pub fn open(path: &Path, git_dir: Option<&Path>) -> Result<Repository> {
+    let repo = match git_dir {
+        Some(git_dir) => {
+            let repo = Repository::open(git_dir)?;
+            // a separate git dir doesn't always know where its work tree is
+            if repo.workdir().is_none() && git_dir != path {
+                repo.set_workdir(path, false)?;
+            }
+            repo
+        }
+        None => Repository::open(path)?,
+    };
+    Ok(repo)
+}
+
+pub fn status(path: &Path, git_dir: Option<&Path>) -> Result<RepoStatus> {
+    let mut repo = open(path, git_dir).with_context(|| format!("Could not open {path:?}"))?;
+
+    let changed = if repo.is_bare() {
+        0
+    } else {
+        let (modified, untracked) = worktree_changes(&repo)?;
+        modified + untracked
+    };
+
+    let ahead_behind = ahead_behind(&repo)?;
+
+    let stashes = stash_count(&mut repo)?;
+
+    Ok(RepoStatus {
+        changed,
+        ahead_behind,
+        stashes,
+    })
+}
+
+pub fn stash_count(repo: &mut Repository) -> Result<usize> {
+    let mut stashes = 0;
+    repo.stash_foreach(|_, _, _| {
+        stashes += 1;
+        true
+    })?;
+    Ok(stashes)
+}
+
+/// Files with uncommitted changes, staged or not, and untracked files
+pub fn worktree_changes(repo: &Repository) -> Result<(usize, usize)> {
+    if let Some(workdir) = repo.workdir().filter(|_| has_fast_status(repo)) {
+        // libgit2 below still knows the answer when git can't be run
+        if let Some(changes) = git_status(repo.path(), workdir) {
+            return Ok(changes);
+        }
+    }
+
+    let statuses = repo.statuses(Some(
+        StatusOptions::new()
+            .include_untracked(true)
+            .include_ignored(false)
+            .exclude_submodules(true),
+    ))?;
+
+    let untracked = statuses.iter().filter(|s| s.status().is_wt_new()).count();
+    Ok((statuses.len() - untracked, untracked))
+}
+
+/// Whether the repo is set up for git to find changes without a full walk: an fsmonitor daemon or
+/// hook telling which files changed, or the untracked cache. libgit2 uses neither
+fn has_fast_status(repo: &Repository) -> bool {
+    let config = match repo.config() {
+        Ok(config) => config,
+        Err(_) => return false,
+    };
+    // true for the builtin daemon, or the path of a hook
+    let fsmonitor = config.get_bool("core.fsmonitor").unwrap_or_else(|_| {
+        config
+            .get_string("core.fsmonitor")
+            .is_ok_and(|hook| !hook.is_empty())
+    });
+    fsmonitor || config.get_bool("core.untrackedCache").unwrap_or(false)
+}
+
+/// [`worktree_changes`] through `git status`, None when it fails
+fn git_status(git_dir: &Path, workdir: &Path) -> Option<(usize, usize)> {
+    let output = Command::new("git")
+        // a background scan mustn't take index.lock from under the user's git
+        .arg("--no-optional-locks")
+        .arg("--git-dir")
+        .arg(git_dir)
+        .arg("--work-tree")
+        .arg(workdir)
+        .args([
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--untracked-files=normal",
+            "--ignore-submodules=all",
+        ])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| parse_porcelain(&output.stdout))
+}
+
+/// Changed and untracked files in `git status --porcelain -z` output
+fn parse_porcelain(out: &[u8]) -> (usize, usize) {
+    let mut entries = out.split(|b| *b == 0).filter(|e| !e.is_empty());
+    let (mut modified, mut untracked) = (0, 0);
+    while let Some(entry) = entries.next() {
+        match entry {
+            [b'?', b'?', ..] => untracked += 1,
+            [x, ..] => {
+                modified += 1;
+                // renames and copies are followed by the path they came from
+                if matches!(x, b'R' | b'C') {
+                    entries.next();
+                }
+            }
+            [] => {}
+        }
+    }
+    (modified, untracked)
+}
+
+/// Commits ahead of and behind the upstream of the checked out branch, None without one
+pub fn ahead_behind(repo: &Repository) -> Result<Option<(usize, usize)>> {
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => head,
+        // detached or unborn
+        _ => return Ok(None),
+    };
+    let branch = repo.find_branch(head.shorthand().unwrap_or_default(), BranchType::Local)?;
+    let upstream = match branch.upstream() {
+        Ok(upstream) => upstream,
+        Err(_) => return Ok(None),
+    };
+
+    match (head.target(), upstream.get().target()) {
+        (Some(local), Some(remote)) => Ok(Some(repo.graph_ahead_behind(local, remote)?)),
+        _ => Ok(None),
+    }
+}
+
+/// Fetch url of the `upstream` remote when the repo has an `origin` too, None for other setups
+pub fn fork_parent(repo: &Repository) -> Option<String> {
+    repo.find_remote("origin").ok()?;
+    let upstream = repo.find_remote("upstream").ok()?;
+    upstream.url().map(|u| u.to_owned())
+}
+
+/// Commits ahead of and behind the `upstream` remote: its branch of the same name as the checked
+/// out one, or the branch its HEAD points at. None when neither was fetched
+pub fn ahead_behind_parent(repo: &Repository) -> Option<(usize, usize)> {
+    let local = repo.head().ok()?.target()?;
+    let parent = current_branch(repo)
+        .and_then(|b| {
+            repo.refname_to_id(&format!("refs/remotes/upstream/{b}"))
+                .ok()
+        })
+        .or_else(|| {
+            repo.find_reference("refs/remotes/upstream/HEAD")
+                .ok()?
+                .resolve()
+                .ok()?
+                .target()
+        })?;
+    repo.graph_ahead_behind(local, parent).ok()
+}
+
+/// Commits on local branches that no remote-tracking branch contains, all of them without remotes
+pub fn unpushed(repo: &Repository) -> Result<usize> {
+    let mut walk = repo.revwalk()?;
+    walk.push_glob("refs/heads")?;
+    walk.hide_glob("refs/remotes")?;
+    Ok(walk.count())
+}
+
+/// Name of the checked out branch, None when HEAD is detached
+pub fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+    head.shorthand().map(|s| s.to_owned())
+}
+
+/// Branch origin/HEAD points at, which is the default branch of the remote at clone time
+pub fn default_branch(repo: &Repository) -> Option<String> {
+    let origin_head = repo.find_reference("refs/remotes/origin/HEAD").ok()?;
+    let target = origin_head.symbolic_target()?;
+    target
+        .strip_prefix("refs/remotes/origin/")
+        .map(|s| s.to_owned())
+}
+
+/// Commit HEAD points at, None in a repo without commits
+pub fn head_commit(repo: &Repository) -> Option<String> {
+    let head = repo.head().ok()?;
+    head.target().map(|id| id.to_string())
+}
+
+/// Closest tag HEAD descends from and how many commits HEAD is past it, None without one
+pub fn latest_tag(repo: &Repository) -> Option<(String, usize)> {
+    let describe = repo.describe(DescribeOptions::new().describe_tags()).ok()?;
+    // the tag name alone, like `git describe --abbrev=0`
+    let tag = describe
+        .format(Some(DescribeFormatOptions::new().abbreviated_size(0)))
+        .ok()?;
+
+    let head = repo.head().ok()?.target()?;
+    let tagged = repo
+        .revparse_single(&format!("refs/tags/{tag}"))
+        .ok()?
+        .peel_to_commit()
+        .ok()?;
+    let (since, _) = repo.graph_ahead_behind(head, tagged.id()).ok()?;
+    Some((tag, since))
+}
+
+/// When git last wrote FETCH_HEAD, which every fetch and pull does. None for repos that never
+/// fetched
+pub fn last_fetch(repo: &Repository) -> Option<DateTime<Utc>> {
+    // a linked work tree has its own, the main one is in the common dir it points to
+    let common = fs::read_to_string(repo.path().join("commondir"))
+        .ok()
+        .map(|dir| repo.path().join(dir.trim()));
+    [Some(repo.path().to_owned()), common]
+        .iter()
+        .flatten()
+        .filter_map(|dir| fs::metadata(dir.join("FETCH_HEAD")).ok()?.modified().ok())
+        .max()
+        .map(DateTime::from)
+}
+
+/// Linked work trees of the repo, those whose directory is gone are left out
+pub fn worktrees(repo: &Repository) -> Result<Vec<Worktree>> {
+    let mut found = Vec::new();
+    for name in repo.worktrees()?.iter().flatten() {
+        let worktree = match repo.find_worktree(name) {
+            Ok(worktree) => worktree,
+            Err(_) => continue,
+        };
+        let linked = match Repository::open_from_worktree(&worktree) {
+            Ok(linked) => linked,
+            Err(_) => continue,
+        };
+        found.push(Worktree {
+            path: worktree.path().to_string_lossy().into_owned(),
+            branch: current_branch(&linked),
+        });
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain() {
+        let out = b" M src/main.rs\0R  new.rs\0old.rs\0A  added.rs\0?? notes.txt\0?? tmp/\0";
+        assert_eq!(parse_porcelain(out), (3, 2));
+        assert_eq!(parse_porcelain(b""), (0, 0));
+    }
+}
@@ -0,0 +1,537 @@
+//! Where the cache is persisted.
+//!
+//! The JSON file is the default and is easy to read from other tools. The SQLite database keeps
+//! one row per repo, so single updates don't rewrite everything and date queries use an index.
+//!
+//! Several pplaces can run at once, say a scan from cron while cloning by hand. The JSON file is
+//! guarded by an advisory lock on `.cache.json.lock` and replaced atomically, SQLite has locking of
+//! its own. Commands that work on a copy of the cache for long, like a scan, save it with
+//! [`CacheStore::save_merged`] so what others wrote meanwhile isn't lost.
+//!
+//! Both record the `SCHEMA_VERSION` they were written with, in the JSON document and in SQLite's
+//! `user_version`. Entries of older versions go through `migrate` when they are read, and an entry
+//! that still can't be read is skipped instead of losing the whole cache.
+
+use crate::{data_dir, keep_user_fields, Cache, ProjectMetadata};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ArgEnum;
+use rusqlite::{params, Connection, TransactionBehavior};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+pub enum StoreKind {
+    /// A single `.cache.json` file
+    Json,
+    /// An SQLite database, `.cache.sqlite`
+    Sqlite,
+}
+
+static KIND: OnceLock<StoreKind> = OnceLock::new();
+
+/// Layout of the cached entries. Changing the shape of one bumps it and adds a step to `migrate`.
+///
+/// 1. a bare array of entries
+/// 2. `{"version": 2, "repos": [...]}`, the entries didn't change
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Upgrades an entry written with `from` to the current version
+fn migrate(_entry: &mut Value, from: u32) {
+    for version in from..SCHEMA_VERSION {
+        match version {
+            // 1 to 2 only wrapped the array
+            1 => {}
+            _ => unreachable!("no migration from version {version}"),
+        }
+    }
+}
+
+/// Reads entries written with `version`, skipping the ones that can't be read
+fn read_entries(entries: Vec<Value>, version: u32) -> Result<Cache> {
+    if version > SCHEMA_VERSION {
+        bail!("The cache was written by a newer pplaces (schema version {version}), update it");
+    }
+
+    let mut cache = Vec::new();
+    for mut entry in entries {
+        migrate(&mut entry, version);
+        match serde_json::from_value(entry) {
+            Ok(entry) => cache.push(entry),
+            Err(err) => warn!("Skipping a cache entry that can't be read: {err}"),
+        }
+    }
+    Ok(cache)
+}
+
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u32,
+    repos: &'a Cache,
+}
+
+/// The cache as a versioned JSON document
+pub fn to_json(cache: &Cache) -> Result<String> {
+    Ok(serde_json::to_string(&Versioned {
+        version: SCHEMA_VERSION,
+        repos: cache,
+    })?)
+}
+
+/// Reads a JSON cache of any version
+pub fn from_json(json: &str) -> Result<Cache> {
+    let (version, entries) = match serde_json::from_str::<Value>(json)? {
+        // before versions were recorded
+        Value::Array(entries) => (1, entries),
+        Value::Object(mut doc) => {
+            let version = doc
+                .get("version")
+                .and_then(|v| v.as_u64())
+                .context("The cache has no version")?;
+            let entries = match doc.remove("repos") {
+                Some(Value::Array(entries)) => entries,
+                _ => bail!("The cache has no repos"),
+            };
+            (version as u32, entries)
+        }
+        _ => bail!("The cache is neither an array nor an object"),
+    };
+    read_entries(entries, version)
+}
+
+pub fn set_kind(kind: StoreKind) {
+    let _ = KIND.set(kind);
+}
+
+pub trait CacheStore {
+    /// Every repo, newest first
+    fn load(&self) -> Result<Cache>;
+    /// Replaces the whole cache
+    fn save(&mut self, cache: &Cache) -> Result<()>;
+    /// Adds the entries or replaces the ones with the same path
+    fn upsert(&mut self, entries: &[ProjectMetadata]) -> Result<()>;
+    /// Replaces the whole cache with `cache`, which was made from `base`. Changes written since
+    /// `base` was loaded are merged into `cache` first, see [`merge_concurrent`]
+    fn save_merged(&mut self, base: &Cache, cache: &mut Cache) -> Result<()>;
+    /// Repos with a commit after `since`
+    fn recent(&self, since: DateTime<Utc>) -> Result<Cache> {
+        let mut cache = self.load()?;
+        cache.retain(|e| {
+            e.latest_commit
+                .is_some_and(|d| d.naive_utc() >= since.naive_utc())
+        });
+        Ok(cache)
+    }
+}
+
+/// Brings what others wrote to the store since `base` was loaded into `ours`, the cache a long
+/// running command made from `base`. Repos they added are kept, repos they removed are dropped.
+/// Repos they changed keep their tags and forge info, or replace ours when we didn't read them
+/// again
+pub fn merge_concurrent(base: &Cache, ours: &mut Cache, theirs: Cache) {
+    let as_json = |e: &ProjectMetadata| serde_json::to_value(e).ok();
+    let base = base
+        .iter()
+        .map(|e| (e.path.as_str(), e))
+        .collect::<HashMap<_, _>>();
+    let stored = theirs
+        .iter()
+        .map(|e| e.path.clone())
+        .collect::<HashSet<_>>();
+    ours.retain(|e| !base.contains_key(e.path.as_str()) || stored.contains(&e.path));
+
+    for e in theirs {
+        let old = base.get(e.path.as_str());
+        if old.is_some_and(|old| as_json(old) == as_json(&e)) {
+            continue;
+        }
+        match ours.iter_mut().find(|o| o.path == e.path) {
+            Some(o) if old.is_some_and(|old| as_json(old) == as_json(o)) => *o = e,
+            Some(o) => keep_user_fields(o, &e),
+            // dropped by us, pruned or removed
+            None if old.is_some() => {}
+            None => ours.push(e),
+        }
+    }
+}
+
+/// Opens the store picked with `--store`
+pub fn open() -> Result<Box<dyn CacheStore>> {
+    let dir = data_dir().context("No data directory")?;
+    fs::create_dir_all(&dir)?;
+
+    Ok(match *KIND.get().unwrap_or(&StoreKind::Json) {
+        StoreKind::Json => Box::new(JsonStore {
+            path: dir.join(".cache.json"),
+        }),
+        StoreKind::Sqlite => Box::new(SqliteStore::open(dir.join(".cache.sqlite"))?),
+    })
+}
+
+/// Advisory lock on a file next to the cache, released when dropped
+struct CacheLock {
+    _file: File,
+}
+
+impl CacheLock {
+    /// Waits for the lock, shared ones for reading and an exclusive one for writing
+    fn acquire(cache: &Path, exclusive: bool) -> Result<CacheLock> {
+        let path = cache.with_extension("json.lock");
+        let file = File::create(&path).with_context(|| format!("Could not open {path:?}"))?;
+        flock(&file, exclusive).with_context(|| format!("Could not lock {path:?}"))?;
+        Ok(CacheLock { _file: file })
+    }
+}
+
+#[cfg(unix)]
+fn flock(file: &File, exclusive: bool) -> std::io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let op = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), op) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn flock(file: &File, exclusive: bool) -> std::io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::{
+        Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK},
+        System::IO::OVERLAPPED,
+    };
+
+    let flags = if exclusive {
+        LOCKFILE_EXCLUSIVE_LOCK
+    } else {
+        0
+    };
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    // the whole file, released when the handle is closed
+    let locked = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            flags,
+            0,
+            u32::MAX,
+            u32::MAX,
+            &mut overlapped,
+        )
+    };
+    if locked == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn flock(_file: &File, _exclusive: bool) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "file locking is not supported on this platform, use --store sqlite",
+    ))
+}
+
+pub struct JsonStore {
+    path: PathBuf,
+}
+
+impl JsonStore {
+    fn read(&self) -> Result<Cache> {
+        let data_str = fs::read_to_string(&self.path).context("Cache file not found")?;
+        from_json(&data_str)
+    }
+
+    /// Writes a temporary file and renames it over the cache, so readers never see half of it
+    fn write(&self, cache: &Cache) -> Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        // this is written as a JSON because it's easier to interface with web technologies
+        fs::write(&tmp, to_json(cache)?)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl CacheStore for JsonStore {
+    fn load(&self) -> Result<Cache> {
+        let _lock = CacheLock::acquire(&self.path, false)?;
+        self.read()
+    }
+
+    fn save(&mut self, cache: &Cache) -> Result<()> {
+        let _lock = CacheLock::acquire(&self.path, true)?;
+        self.write(cache)
+    }
+
+    fn upsert(&mut self, entries: &[ProjectMetadata]) -> Result<()> {
+        // held across the read, entries written by others in between would be lost
+        let _lock = CacheLock::acquire(&self.path, true)?;
+        let mut cache = self.read().unwrap_or_default();
+        for entry in entries {
+            crate::upsert_repo(entry.clone(), &mut cache);
+        }
+        self.write(&cache)
+    }
+
+    fn save_merged(&mut self, base: &Cache, cache: &mut Cache) -> Result<()> {
+        let _lock = CacheLock::acquire(&self.path, true)?;
+        merge_concurrent(base, cache, self.read().unwrap_or_default());
+        self.write(cache)
+    }
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the database, importing the JSON cache the first time.
+    pub fn open(path: PathBuf) -> Result<SqliteStore> {
+        let fresh = !path.exists();
+        let conn = Connection::open(&path).with_context(|| format!("Could not open {path:?}"))?;
+        // wait for other pplaces writing at the same time instead of failing
+        conn.busy_timeout(Duration::from_secs(30))?;
+
+        // `data` holds the whole entry as JSON, the other columns are only there to be queried
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS repos (
+                path TEXT PRIMARY KEY,
+                latest_commit TEXT,
+                data TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS repos_latest_commit ON repos (latest_commit);",
+        )?;
+
+        let version = store_version(&conn)?;
+        let mut store = SqliteStore { conn };
+        if version < SCHEMA_VERSION {
+            if !fresh {
+                // rows are read through the migrations and written back
+                let cache = store.load()?;
+                store.save(&cache)?;
+            }
+            store
+                .conn
+                .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        }
+        if fresh {
+            let json = JsonStore {
+                path: path.with_file_name(".cache.json"),
+            };
+            if let Ok(cache) = json.load() {
+                store.save(&cache)?;
+            }
+        }
+
+        Ok(store)
+    }
+}
+
+fn query(conn: &Connection, sql: &str, params: impl rusqlite::Params) -> Result<Cache> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params, |row| row.get::<_, String>(0))?;
+
+    let mut entries = Vec::new();
+    for data in rows {
+        entries.push(serde_json::from_str(&data?)?);
+    }
+    read_entries(entries, store_version(conn)?)
+}
+
+const LOAD_SQL: &str = "SELECT data FROM repos ORDER BY latest_commit DESC";
+
+/// Schema version of the rows, databases from before it was recorded have 0 which is version 1
+fn store_version(conn: &Connection) -> Result<u32> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(version.max(1))
+}
+
+/// UTC, so it sorts the same as the instants it comes from
+fn date_key(date: Option<NaiveDateTime>) -> Option<String> {
+    date.map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Replaces every row with the entries of `cache`
+fn replace_rows(conn: &Connection, cache: &Cache) -> Result<()> {
+    conn.execute("DELETE FROM repos", params![])?;
+    for entry in cache {
+        upsert_row(conn, entry)?;
+    }
+    Ok(())
+}
+
+fn upsert_row(conn: &Connection, entry: &ProjectMetadata) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO repos (path, latest_commit, data) VALUES (?1, ?2, ?3)",
+        params![
+            entry.path,
+            date_key(entry.latest_commit.map(|d| d.naive_utc())),
+            serde_json::to_string(entry)?
+        ],
+    )?;
+    Ok(())
+}
+
+impl CacheStore for SqliteStore {
+    fn load(&self) -> Result<Cache> {
+        query(&self.conn, LOAD_SQL, params![])
+    }
+
+    fn save(&mut self, cache: &Cache) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        replace_rows(&tx, cache)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn upsert(&mut self, entries: &[ProjectMetadata]) -> Result<()> {
+        // like the JSON store, a fresh read keeps the tags and forge info of the stored entry
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for entry in entries {
+            let mut entry = entry.clone();
+            let stored = query(&tx, "SELECT data FROM repos WHERE path = ?1", [&entry.path])?;
+            if let Some(old) = stored.first() {
+                keep_user_fields(&mut entry, old);
+            }
+            upsert_row(&tx, &entry)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_merged(&mut self, base: &Cache, cache: &mut Cache) -> Result<()> {
+        // takes the write lock before reading, nobody can write between the read and the save
+        let tx = self
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        merge_concurrent(base, cache, query(&tx, LOAD_SQL, params![])?);
+        replace_rows(&tx, cache)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn recent(&self, since: DateTime<Utc>) -> Result<Cache> {
+        query(
+            &self.conn,
+            "SELECT data FROM repos WHERE latest_commit >= ?1 ORDER BY latest_commit DESC",
+            params![date_key(Some(since.naive_utc()))],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let old = r#"[{"path": "/src/a", "upstream": []}, {"path": 3}]"#;
+        let cache = from_json(old).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache[0].path, "/src/a");
+
+        let cache = from_json(&to_json(&cache).unwrap()).unwrap();
+        assert_eq!(cache[0].path, "/src/a");
+
+        assert!(from_json(r#"{"version": 99, "repos": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_upsert() {
+        let dir = std::env::temp_dir().join(format!("pplaces-store-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let repo = |path: &str, tags: &[&str]| ProjectMetadata {
+            path: path.to_owned(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let stores: [Box<dyn CacheStore>; 2] = [
+            Box::new(JsonStore {
+                path: dir.join(".cache.json"),
+            }),
+            Box::new(SqliteStore::open(dir.join(".cache.sqlite")).unwrap()),
+        ];
+
+        for mut store in stores {
+            store.save(&vec![repo("/src/a", &["work"])]).unwrap();
+            store
+                .upsert(&[repo("/src/b", &[]), repo("/src/a", &[])])
+                .unwrap();
+            let mut cache = store.load().unwrap();
+            cache.sort_by(|a, b| a.path.cmp(&b.path));
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache[0].tags, ["work"]);
+            assert_eq!(cache[1].path, "/src/b");
+        }
+        assert!(!dir.join(".cache.json.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_concurrent() {
+        let repo = |path: &str, summary: &str, tags: &[&str]| ProjectMetadata {
+            path: path.to_owned(),
+            last_commit_summary: Some(summary.to_owned()),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        let base = vec![
+            repo("/a", "old", &[]),
+            repo("/b", "old", &[]),
+            repo("/c", "old", &[]),
+            repo("/d", "old", &[]),
+        ];
+        // a scan read /a and /e again, pruned /d and left /b and /c as they were
+        let mut ours = vec![
+            repo("/a", "new", &[]),
+            repo("/b", "old", &[]),
+            repo("/c", "old", &[]),
+            repo("/e", "new", &[]),
+        ];
+        // meanwhile /a was tagged, a clone updated /b and added /f, /c was removed
+        let theirs = vec![
+            repo("/a", "old", &["work"]),
+            repo("/b", "cloned", &[]),
+            repo("/d", "old", &[]),
+            repo("/f", "cloned", &[]),
+        ];
+
+        merge_concurrent(&base, &mut ours, theirs);
+        let merged = ours
+            .iter()
+            .map(|e| {
+                let summary = e.last_commit_summary.clone().unwrap();
+                (e.path.as_str(), summary, e.tags.clone())
+            })
+            .collect::<Vec<_>>();
+        let entry = |path, summary: &str, tags: &[&str]| {
+            let tags = tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+            (path, summary.to_owned(), tags)
+        };
+        assert_eq!(
+            merged,
+            [
+                entry("/a", "new", &["work"]),
+                entry("/b", "cloned", &[]),
+                entry("/e", "new", &[]),
+                entry("/f", "cloned", &[]),
+            ]
+        );
+    }
+}
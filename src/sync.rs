@@ -0,0 +1,109 @@
+//! Sharing caches between machines.
+//!
+//! Every machine publishes its cache as `<hostname>.json` to a shared place, either a git repo or
+//! an rsync target, and keeps a copy of everyone else's under `machines/` in the data dir.
+
+use crate::{data_dir, hostname, run_git, Cache, RemoteUrl};
+use anyhow::{bail, Context, Result};
+use std::{ffi::OsStr, fs, path::PathBuf, process::Command};
+
+fn machines_dir() -> Result<PathBuf> {
+    let dir = data_dir().context("No data directory")?.join("machines");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// git urls go through a shared repo, anything else is handed to rsync
+fn is_git_remote(via: &str) -> bool {
+    via.ends_with(".git") || via.starts_with("git@") || via.contains("://")
+}
+
+/// Publishes our cache and fetches the other machines', returning their names.
+pub fn sync(via: &str, cache: &Cache) -> Result<Vec<String>> {
+    let own = format!("{}.json", hostname());
+    let json = crate::store::to_json(cache)?;
+    let machines = machines_dir()?;
+
+    if is_git_remote(via) {
+        let data = data_dir().context("No data directory")?;
+        let repo = data.join("sync");
+        if !repo.join(".git").exists() {
+            run_git(&data, &["clone", via, "sync"])?;
+        } else if !run_git(&repo, &["ls-remote", "--heads", "origin"])?.is_empty() {
+            run_git(&repo, &["pull", "--rebase"])?;
+        }
+
+        fs::write(repo.join(&own), &json)?;
+        if !run_git(&repo, &["status", "--porcelain"])?.is_empty() {
+            run_git(&repo, &["add", &own])?;
+            run_git(&repo, &["commit", "-m", &format!("Update {own}")])?;
+        }
+        run_git(&repo, &["push", "origin", "HEAD"])?;
+
+        for e in fs::read_dir(&repo)?.flatten() {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if name.ends_with(".json") && name != own {
+                fs::copy(e.path(), machines.join(&name))?;
+            }
+        }
+    } else {
+        let ours = machines.join(&own);
+        fs::write(&ours, &json)?;
+        let target = via.trim_end_matches('/');
+        rsync(&[ours.as_os_str(), OsStr::new(&format!("{target}/{own}"))])?;
+        // the trailing slash copies what is inside, not the directory itself
+        let mut into = machines.into_os_string();
+        into.push("/");
+        rsync(&[
+            OsStr::new("--exclude"),
+            OsStr::new(&own),
+            OsStr::new(&format!("{target}/")),
+            &into,
+        ])?;
+    }
+
+    machine_names()
+}
+
+/// Other machines we have a synced cache of
+pub fn machine_names() -> Result<Vec<String>> {
+    let mut names = fs::read_dir(machines_dir()?)?
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            name.strip_suffix(".json").map(|n| n.to_owned())
+        })
+        .filter(|n| *n != hostname())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
+
+fn rsync(args: &[&OsStr]) -> Result<()> {
+    let output = Command::new("rsync")
+        .arg("-a")
+        .args(args)
+        .output()
+        .context("Failed to run rsync")?;
+    if !output.status.success() {
+        bail!(
+            "rsync failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// The last synced cache of another machine
+pub fn load_machine(name: &str) -> Result<Cache> {
+    let path = machines_dir()?.join(format!("{name}.json"));
+    let json = fs::read_to_string(&path)
+        .with_context(|| format!("No synced cache for {name}, run sync-cache first"))?;
+    crate::store::from_json(&json)
+}
+
+/// `host/owner/repo`, the same repo cloned on different machines has the same key
+pub fn remote_key(url: &str) -> Option<String> {
+    RemoteUrl::parse(url).map(|r| r.key())
+}
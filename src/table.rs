@@ -0,0 +1,183 @@
+//! Aligned table for `show --table`.
+//!
+//! One row per repo with its path relative to the current directory or home, the age of the last
+//! commit, the checked out branch, a `*` when there are uncommitted changes, the first remote and
+//! who wrote the last commit and its subject.
+
+use crate::{theme::Theme, Cache, ProjectMetadata, RemoteUrl};
+use chrono::{DateTime, FixedOffset, Local};
+use std::{env, path::Path};
+
+/// Longest commit subject shown before it's cut with `…`
+const MESSAGE_WIDTH: usize = 50;
+
+struct Row {
+    path: String,
+    age: String,
+    /// Days since the last commit, `None` without commits
+    days: Option<i64>,
+    branch: String,
+    dirty: bool,
+    remote: String,
+    message: String,
+}
+
+/// Plain text without a `theme`
+pub fn print(data: &Cache, theme: Option<&Theme>) {
+    let now: DateTime<FixedOffset> = Local::now().into();
+    let rows = data.iter().map(|e| row(e, now)).collect::<Vec<_>>();
+
+    let path_w = width("PATH", rows.iter().map(|r| r.path.as_str()));
+    let age_w = width("LAST COMMIT", rows.iter().map(|r| r.age.as_str()));
+    let branch_w = width("BRANCH", rows.iter().map(|r| r.branch.as_str()));
+    let remote_w = width("REMOTE", rows.iter().map(|r| r.remote.as_str()));
+
+    let header = format!(
+        "{:path_w$}  {:age_w$}  {:branch_w$}  {:1}  {:remote_w$}  MESSAGE",
+        "PATH", "LAST COMMIT", "BRANCH", "", "REMOTE"
+    );
+    match theme {
+        Some(t) => println!("{}", t.header.apply(header)),
+        None => println!("{header}"),
+    }
+
+    for r in &rows {
+        // pad before coloring, the escape codes would count towards the width
+        let path = format!("{:path_w$}", r.path);
+        let age = format!("{:age_w$}", r.age);
+        let branch = format!("{:branch_w$}", r.branch);
+        let dirty = if r.dirty { "*" } else { " " };
+        let remote = format!("{:remote_w$}", r.remote);
+        match theme {
+            Some(t) => {
+                let age_style = match r.days {
+                    Some(d) if d < 7 => t.age_fresh,
+                    Some(d) if d < 90 => t.age_recent,
+                    _ => t.age_old,
+                };
+                println!(
+                    "{}  {}  {}  {}  {}  {}",
+                    t.path.apply(path),
+                    age_style.apply(age),
+                    t.branch.apply(branch),
+                    t.dirty.apply(dirty),
+                    t.remote.apply(remote),
+                    r.message
+                );
+            }
+            None => println!("{path}  {age}  {branch}  {dirty}  {remote}  {}", r.message),
+        }
+    }
+}
+
+fn width<'a>(header: &str, cells: impl Iterator<Item = &'a str>) -> usize {
+    cells
+        .map(|c| c.chars().count())
+        .chain([header.chars().count()])
+        .max()
+        .unwrap_or_default()
+}
+
+fn row(e: &ProjectMetadata, now: DateTime<FixedOffset>) -> Row {
+    let remote = e
+        .upstream
+        .iter()
+        .find_map(|u| u.strip_suffix(" (fetch)"))
+        .map(|url| match RemoteUrl::parse(url) {
+            Some(remote) => format!("{}/{}", remote.host, remote.path),
+            None => url.to_owned(),
+        });
+    let summary = truncate(
+        e.last_commit_summary.as_deref().unwrap_or_default(),
+        MESSAGE_WIDTH,
+    );
+    let message = match &e.last_commit_author {
+        Some(author) => format!("{author}: {summary}"),
+        None => summary,
+    };
+
+    Row {
+        path: display_path(Path::new(&e.path)),
+        age: e
+            .latest_commit
+            .map(|d| ago(now, d))
+            .unwrap_or_else(|| "never".to_owned()),
+        days: e
+            .latest_commit
+            .map(|d| now.signed_duration_since(d).num_days()),
+        branch: e.current_branch.clone().unwrap_or_else(|| "-".to_owned()),
+        dirty: e.dirty,
+        remote: remote.unwrap_or_default(),
+        message,
+    }
+}
+
+/// Relative to the current directory when inside it, otherwise with `~` for home
+fn display_path(path: &Path) -> String {
+    if let Ok(cwd) = env::current_dir() {
+        if let Ok(rel) = path.strip_prefix(&cwd) {
+            if !rel.as_os_str().is_empty() {
+                return rel.display().to_string();
+            }
+        }
+    }
+    if let Some(home) = dirs::home_dir() {
+        if let Ok(rel) = path.strip_prefix(&home) {
+            return Path::new("~").join(rel).display().to_string();
+        }
+    }
+    path.display().to_string()
+}
+
+/// At most `max` characters of `text`, the last one a `…` when it had to be cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
+    }
+    let mut cut = text.chars().take(max - 1).collect::<String>();
+    cut.push('…');
+    cut
+}
+
+/// "5m ago", "3d ago", "2y ago"...
+fn ago(now: DateTime<FixedOffset>, then: DateTime<FixedOffset>) -> String {
+    let elapsed = now.signed_duration_since(then);
+    let minutes = elapsed.num_minutes();
+    let (n, unit) = match minutes {
+        m if m < 1 => return "just now".to_owned(),
+        m if m < 60 => (m, "m"),
+        m if m < 60 * 24 => (m / 60, "h"),
+        m if m < 60 * 24 * 14 => (m / (60 * 24), "d"),
+        m if m < 60 * 24 * 60 => (m / (60 * 24 * 7), "w"),
+        m if m < 60 * 24 * 365 => (m / (60 * 24 * 30), "mo"),
+        m => (m / (60 * 24 * 365), "y"),
+    };
+    format!("{n}{unit} ago")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ago() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00+00:00").unwrap();
+        let ago = |date: &str| ago(now, DateTime::parse_from_rfc3339(date).unwrap());
+
+        assert_eq!(ago("2024-06-01T11:59:30+00:00"), "just now");
+        assert_eq!(ago("2024-06-01T11:15:00+00:00"), "45m ago");
+        assert_eq!(ago("2024-06-01T14:00:00+05:00"), "3h ago");
+        assert_eq!(ago("2024-05-29T12:00:00+00:00"), "3d ago");
+        assert_eq!(ago("2024-05-01T12:00:00+00:00"), "4w ago");
+        assert_eq!(ago("2024-01-01T12:00:00+00:00"), "5mo ago");
+        assert_eq!(ago("2021-06-01T12:00:00+00:00"), "3y ago");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Fix the thing", 20), "Fix the thing");
+        assert_eq!(truncate("Fix the thing", 13), "Fix the thing");
+        assert_eq!(truncate("Fix the thing", 8), "Fix the…");
+        assert_eq!(truncate("Añadir más", 6), "Añadi…");
+    }
+}
@@ -0,0 +1,138 @@
+//! Styles of the colored output, from the `[theme]` section of the config.
+//!
+//! Color is only used on a terminal and when `NO_COLOR` isn't set, see [`use_color`]. The presets
+//! are a starting point, every style set in the config replaces the one of the preset.
+
+use crate::config::{ThemeConfig, ThemePreset};
+use anyhow::{bail, Result};
+use crossterm::style::{Attribute, Color, ContentStyle};
+use std::{
+    env,
+    io::{stdout, IsTerminal},
+};
+
+pub struct Theme {
+    pub header: ContentStyle,
+    pub path: ContentStyle,
+    pub age_fresh: ContentStyle,
+    pub age_recent: ContentStyle,
+    pub age_old: ContentStyle,
+    pub branch: ContentStyle,
+    pub dirty: ContentStyle,
+    pub remote: ContentStyle,
+    pub heat: ContentStyle,
+    pub heat_empty: ContentStyle,
+}
+
+/// Whether stdout gets colors: it is a terminal and `NO_COLOR` is unset or empty
+pub fn use_color() -> bool {
+    stdout().is_terminal() && env::var_os("NO_COLOR").unwrap_or_default().is_empty()
+}
+
+impl Theme {
+    pub fn new(config: &ThemeConfig) -> Result<Theme> {
+        let mut theme = Theme::preset(config.preset);
+        let styles = [
+            (&mut theme.header, &config.header),
+            (&mut theme.path, &config.path),
+            (&mut theme.age_fresh, &config.age_fresh),
+            (&mut theme.age_recent, &config.age_recent),
+            (&mut theme.age_old, &config.age_old),
+            (&mut theme.branch, &config.branch),
+            (&mut theme.dirty, &config.dirty),
+            (&mut theme.remote, &config.remote),
+            (&mut theme.heat, &config.heat),
+            (&mut theme.heat_empty, &config.heat_empty),
+        ];
+        for (style, spec) in styles {
+            if let Some(spec) = spec {
+                *style = parse_style(spec)?;
+            }
+        }
+        Ok(theme)
+    }
+
+    fn preset(preset: ThemePreset) -> Theme {
+        let style = |spec: &str| parse_style(spec).unwrap();
+        match preset {
+            ThemePreset::Default => Theme {
+                header: style("bold"),
+                path: style("bold"),
+                age_fresh: style("dark_grey"),
+                age_recent: style("dark_grey"),
+                age_old: style("dark_grey"),
+                branch: style("cyan"),
+                dirty: style("yellow"),
+                remote: style("dark_grey"),
+                heat: style("green"),
+                heat_empty: style("dark_grey"),
+            },
+            ThemePreset::HighContrast => Theme {
+                header: style("bold underlined"),
+                path: style("bold white"),
+                age_fresh: style("bold blue"),
+                age_recent: style("white"),
+                age_old: style("grey"),
+                branch: style("bold cyan"),
+                dirty: style("bold yellow"),
+                remote: style("white"),
+                heat: style("bold blue"),
+                heat_empty: style("grey"),
+            },
+            ThemePreset::Monochrome => Theme {
+                header: style("bold"),
+                path: style("bold"),
+                age_fresh: style("bold"),
+                age_recent: style("none"),
+                age_old: style("none"),
+                branch: style("none"),
+                dirty: style("bold"),
+                remote: style("none"),
+                heat: style("bold"),
+                heat_empty: style("none"),
+            },
+        }
+    }
+}
+
+/// `bold yellow`, `dark_grey` or `none`
+fn parse_style(spec: &str) -> Result<ContentStyle> {
+    let mut style = ContentStyle::new();
+    for word in spec.split_whitespace() {
+        match word {
+            "none" => {}
+            "bold" => style.attributes.set(Attribute::Bold),
+            "italic" => style.attributes.set(Attribute::Italic),
+            "underlined" => style.attributes.set(Attribute::Underlined),
+            color => match Color::try_from(color) {
+                Ok(color) => style.foreground_color = Some(color),
+                Err(()) => bail!("Unknown color {color:?} in the theme"),
+            },
+        }
+    }
+    Ok(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme() {
+        let config = ThemeConfig {
+            preset: ThemePreset::Monochrome,
+            dirty: Some("bold magenta".to_owned()),
+            ..Default::default()
+        };
+        let theme = Theme::new(&config).unwrap();
+        assert_eq!(theme.dirty.foreground_color, Some(Color::Magenta));
+        assert!(theme.dirty.attributes.has(Attribute::Bold));
+        assert_eq!(theme.branch, ContentStyle::new());
+
+        let config = ThemeConfig {
+            heat: Some("bold purple".to_owned()),
+            ..Default::default()
+        };
+        assert!(Theme::new(&config).is_err());
+    }
+}
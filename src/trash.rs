@@ -0,0 +1,191 @@
+//! Where `remove` puts directories instead of deleting them, so `undo` can bring them back.
+//!
+//! Each removal gets a directory of its own in `trash` inside the data dir and a line in the
+//! `trash` journal, with where the directory came from and the cache entry of its repo. Removals
+//! older than `trash_days` of the config are deleted for good by the next `remove`.
+
+use crate::{data_dir, journal::Journal, ProjectMetadata};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Retention when the config doesn't set `trash_days`
+pub const DEFAULT_DAYS: u32 = 30;
+
+/// Journal state of a removal that isn't in the trash anymore, the others have their JSON
+const RESTORED: &str = "restored";
+const PURGED: &str = "purged";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Removal {
+    pub removed: DateTime<Utc>,
+    /// Where the directory was
+    pub from: PathBuf,
+    /// Where it is in the trash
+    pub to: PathBuf,
+    /// Put back into the cache by `undo`
+    pub entry: Option<ProjectMetadata>,
+}
+
+fn journal() -> Result<Journal> {
+    // never finished, it lives as long as the trash
+    Journal::open("trash", true)
+}
+
+/// Removals still in the trash with their ids, the latest last
+fn removals(journal: &Journal) -> Vec<(String, Removal)> {
+    let mut removals = journal
+        .states()
+        .filter_map(|(id, state)| Some((id.to_owned(), serde_json::from_str(state).ok()?)))
+        .collect::<Vec<_>>();
+    removals.sort_by(|a, b| a.0.cmp(&b.0));
+    removals
+}
+
+pub fn list() -> Result<Vec<Removal>> {
+    Ok(removals(&journal()?).into_iter().map(|(_, r)| r).collect())
+}
+
+/// Moves `dir` into the trash, `entry` is what the cache knew about its repo
+pub fn put(dir: &Path, entry: Option<ProjectMetadata>) -> Result<()> {
+    let removed = Utc::now();
+    let id = removed.format("%Y%m%dT%H%M%S%.6f").to_string();
+    let name = dir.file_name().context("Can't trash a root directory")?;
+    let slot = data_dir()
+        .context("No data directory")?
+        .join("trash")
+        .join(&id);
+    fs::create_dir_all(&slot)?;
+    let to = slot.join(name);
+    move_dir(dir, &to)?;
+
+    let removal = Removal {
+        removed,
+        from: dir.to_path_buf(),
+        to,
+        entry,
+    };
+    journal()?.record(&id, &serde_json::to_string(&removal)?)
+}
+
+/// Moves the latest removal, or the latest one of `path`, back where it was
+pub fn undo(path: Option<&Path>) -> Result<Removal> {
+    let mut journal = journal()?;
+    let mut removals = removals(&journal);
+    let found = match path {
+        Some(path) => removals.into_iter().rev().find(|(_, r)| r.from == path),
+        None => removals.pop(),
+    };
+    let (id, removal) = match (found, path) {
+        (Some(found), _) => found,
+        (None, Some(path)) => bail!("{} is not in the trash", path.display()),
+        (None, None) => bail!("The trash is empty, nothing to undo"),
+    };
+
+    if removal.from.exists() {
+        bail!(
+            "{} exists again, move it away first",
+            removal.from.display()
+        );
+    }
+    if let Some(parent) = removal.from.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    move_dir(&removal.to, &removal.from)?;
+    if let Some(slot) = removal.to.parent() {
+        let _ = fs::remove_dir(slot);
+    }
+    journal.record(&id, RESTORED)?;
+    Ok(removal)
+}
+
+/// Deletes for good what was removed more than `days` ago, returning how many removals went
+pub fn purge(days: u32) -> Result<usize> {
+    let cutoff = Utc::now() - Duration::days(days.into());
+    let mut journal = journal()?;
+    let mut purged = 0;
+    for (id, removal) in removals(&journal) {
+        if removal.removed >= cutoff {
+            continue;
+        }
+        if let Some(slot) = removal.to.parent() {
+            match fs::remove_dir_all(slot) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| format!("Could not delete {slot:?}"));
+                }
+            }
+        }
+        journal.record(&id, PURGED)?;
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+/// `fs::rename`, or a copy and a delete when `to` is on another filesystem
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    copy_dir(from, to)
+        .with_context(|| format!("Could not copy {} to {}", from.display(), to.display()))?;
+    fs::remove_dir_all(from).with_context(|| format!("Could not delete {}", from.display()))
+}
+
+/// Copies the tree at `from`, symlinks are copied as links
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for e in fs::read_dir(from)? {
+        let e = e?;
+        let target = to.join(e.file_name());
+        let kind = e.file_type()?;
+        if kind.is_dir() {
+            copy_dir(&e.path(), &target)?;
+        } else if kind.is_symlink() {
+            copy_link(&e.path(), &target)?;
+        } else {
+            fs::copy(e.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_link(from: &Path, to: &Path) -> io::Result<()> {
+    fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_dir() {
+        let dir = std::env::temp_dir().join(format!("pplaces-trash-{}", std::process::id()));
+        let from = dir.join("repo");
+        fs::create_dir_all(from.join(".git").join("refs")).unwrap();
+        fs::write(from.join(".git").join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(from.join("notes.txt"), "todo").unwrap();
+
+        let to = dir.join("trash").join("repo");
+        copy_dir(&from, &to).unwrap();
+        assert_eq!(fs::read_to_string(to.join("notes.txt")).unwrap(), "todo");
+        assert!(to.join(".git").join("refs").is_dir());
+        assert_eq!(
+            fs::read_to_string(to.join(".git").join("HEAD")).unwrap(),
+            "ref: refs/heads/main\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
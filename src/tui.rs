@@ -0,0 +1,368 @@
+//! Interactive browser for the cached repos.
+//!
+//! Typing filters the list, Tab changes the sort column and Shift-Tab flips the order. Enter opens
+//! a shell in the selected repo, Ctrl-Y copies its path and Ctrl-O opens its remote in a browser.
+//! The line under the list shows what `enrich` found on GitHub about the selected repo.
+
+use crate::{forge::RemoteInfo, fuzzy, Cache, ProjectMetadata, RemoteUrl};
+use anyhow::{Context, Result};
+use chrono::Local;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph, Row, Table, TableState},
+    Terminal,
+};
+use std::{
+    env,
+    io::{stdout, Stdout, Write},
+    path::Path,
+    process::Command,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortBy {
+    LastCommit,
+    Path,
+    Upstream,
+}
+
+impl SortBy {
+    fn next(self) -> SortBy {
+        match self {
+            SortBy::LastCommit => SortBy::Path,
+            SortBy::Path => SortBy::Upstream,
+            SortBy::Upstream => SortBy::LastCommit,
+        }
+    }
+}
+
+struct App {
+    repos: Cache,
+    query: String,
+    sort_by: SortBy,
+    reverse: bool,
+    /// Indexes into `repos` of what is currently shown
+    visible: Vec<usize>,
+    table: TableState,
+    status: String,
+}
+
+impl App {
+    fn refilter(&mut self) {
+        let mut visible = self
+            .repos
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| fuzzy::score(&self.query, &e.path).map(|s| (i, s)))
+            .collect::<Vec<_>>();
+
+        let repos = &self.repos;
+        visible.sort_by(|(a, _), (b, _)| {
+            let (a, b) = (&repos[*a], &repos[*b]);
+            match self.sort_by {
+                // newest first
+                SortBy::LastCommit => b.latest_commit.cmp(&a.latest_commit),
+                SortBy::Path => a.path.cmp(&b.path),
+                SortBy::Upstream => upstream(a).cmp(upstream(b)),
+            }
+        });
+        if self.reverse {
+            visible.reverse();
+        }
+        // best match first, the sort column breaks ties
+        if !self.query.is_empty() {
+            visible.sort_by_key(|(_, s)| std::cmp::Reverse(*s));
+        }
+
+        self.visible = visible.into_iter().map(|(i, _)| i).collect();
+        self.table.select((!self.visible.is_empty()).then_some(0));
+    }
+
+    fn selected(&self) -> Option<&ProjectMetadata> {
+        let i = *self.visible.get(self.table.selected()?)?;
+        Some(&self.repos[i])
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        if self.visible.is_empty() {
+            return;
+        }
+        let i = self.table.selected().unwrap_or(0) as isize + delta;
+        let i = i.clamp(0, self.visible.len() as isize - 1);
+        self.table.select(Some(i as usize));
+    }
+}
+
+/// Fetch url of the first remote, without the `(fetch)` suffix
+fn upstream(e: &ProjectMetadata) -> &str {
+    e.upstream
+        .first()
+        .map(|u| u.split(' ').next().unwrap())
+        .unwrap_or("")
+}
+
+/// What `enrich` found about the repo, like `★ 12  fork  default main  Description`
+fn remote_info_line(info: &RemoteInfo) -> String {
+    let mut parts = vec![format!("★ {}", info.stars)];
+    if info.fork {
+        parts.push("fork".to_owned());
+    }
+    if info.archived {
+        parts.push("archived".to_owned());
+    }
+    if let Some(branch) = &info.default_branch {
+        parts.push(format!("default {branch}"));
+    }
+    if let Some(description) = &info.description {
+        parts.push(description.clone());
+    }
+    parts.join("  ")
+}
+
+/// Author and subject of the last commit, the parent of a fork, then what `enrich` found,
+/// whichever are known
+fn details_line(e: &ProjectMetadata) -> String {
+    let mut parts = Vec::new();
+    match (&e.last_commit_author, &e.last_commit_summary) {
+        (Some(author), Some(summary)) => parts.push(format!("{author}: {summary}")),
+        (None, Some(summary)) => parts.push(summary.clone()),
+        _ => {}
+    }
+    if let Some(parent) = &e.fork_of {
+        parts.push(format!("fork of {parent}"));
+    }
+    if let Some(info) = &e.remote_info {
+        parts.push(remote_info_line(info));
+    }
+    parts.join("  |  ")
+}
+
+pub fn run(repos: Cache) -> Result<()> {
+    let mut app = App {
+        repos,
+        query: String::new(),
+        sort_by: SortBy::LastCommit,
+        reverse: false,
+        visible: Vec::new(),
+        table: TableState::default(),
+        status: String::new(),
+    };
+    app.refilter();
+
+    let mut terminal = enter()?;
+    let res = event_loop(&mut terminal, &mut app);
+    leave()?;
+
+    res
+}
+
+fn enter() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    Ok(Terminal::new(CrosstermBackend::new(stdout()))?)
+}
+
+fn leave() -> Result<()> {
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    Ok(())
+}
+
+fn event_loop(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
+    loop {
+        terminal.draw(|f| draw(f, app))?;
+
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if ctrl => return Ok(()),
+            KeyCode::Up => app.move_by(-1),
+            KeyCode::Char('p') if ctrl => app.move_by(-1),
+            KeyCode::Down => app.move_by(1),
+            KeyCode::Char('n') if ctrl => app.move_by(1),
+            KeyCode::Tab => {
+                app.sort_by = app.sort_by.next();
+                app.reverse = false;
+                app.refilter();
+            }
+            KeyCode::BackTab => {
+                app.reverse = !app.reverse;
+                app.refilter();
+            }
+            KeyCode::Enter => {
+                if let Some(path) = app.selected().map(|e| e.path.clone()) {
+                    leave()?;
+                    let res = open_shell(Path::new(&path));
+                    *terminal = enter()?;
+                    app.status = match res {
+                        Ok(()) => format!("Back from {path}"),
+                        Err(e) => format!("{e:#}"),
+                    };
+                }
+            }
+            KeyCode::Char('y') if ctrl => {
+                if let Some(path) = app.selected().map(|e| e.path.clone()) {
+                    copy_to_clipboard(&path)?;
+                    app.status = format!("Copied {path}");
+                }
+            }
+            KeyCode::Char('o') if ctrl => {
+                if let Some(e) = app.selected() {
+                    app.status = match RemoteUrl::parse(upstream(e)).map(|r| r.web_url()) {
+                        Some(url) => match open_url(&url) {
+                            Ok(()) => format!("Opened {url}"),
+                            Err(e) => format!("{e:#}"),
+                        },
+                        None => "No remote to open".to_owned(),
+                    };
+                }
+            }
+            KeyCode::Backspace => {
+                app.query.pop();
+                app.refilter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                app.query.push(c);
+                app.refilter();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(f: &mut ratatui::Frame, app: &mut App) {
+    let [input, list, details, status] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(1),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(f.size());
+
+    let sort = match app.sort_by {
+        SortBy::LastCommit => "last commit",
+        SortBy::Path => "path",
+        SortBy::Upstream => "upstream",
+    };
+    let title = format!(
+        " {} of {} repos, by {sort}{} ",
+        app.visible.len(),
+        app.repos.len(),
+        if app.reverse { " (reversed)" } else { "" }
+    );
+    f.render_widget(
+        Paragraph::new(app.query.as_str())
+            .block(Block::default().borders(Borders::ALL).title(title)),
+        input,
+    );
+
+    let rows = app.visible.iter().map(|i| {
+        let e = &app.repos[*i];
+        Row::new(vec![
+            e.latest_commit
+                .map(|d| d.with_timezone(&Local).format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            e.path.clone(),
+            e.language.clone().unwrap_or_default(),
+            upstream(e).to_owned(),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(10),
+            Constraint::Percentage(50),
+            Constraint::Length(10),
+            Constraint::Percentage(50),
+        ],
+    )
+    .header(
+        Row::new(vec!["Last commit", "Path", "Language", "Upstream"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(table, list, &mut app.table);
+
+    let info = app.selected().map(details_line).unwrap_or_default();
+    f.render_widget(Paragraph::new(Line::from(info)), details);
+
+    let help = "Enter: shell  Ctrl-Y: copy path  Ctrl-O: open remote  Tab: sort  Esc: quit";
+    let status_line = if app.status.is_empty() {
+        help
+    } else {
+        &app.status
+    };
+    f.render_widget(Paragraph::new(Line::from(status_line)), status);
+}
+
+fn open_shell(path: &Path) -> Result<()> {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_owned());
+    Command::new(&shell)
+        .current_dir(path)
+        .status()
+        .with_context(|| format!("Could not run {shell}"))?;
+    Ok(())
+}
+
+/// Sets the clipboard with the OSC 52 escape sequence, which also works over ssh.
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut out = stdout();
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()?;
+    Ok(())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().fold(0u32, |n, b| n << 8 | *b as u32) << (8 * (3 - chunk.len()));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn open_url(url: &str) -> Result<()> {
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener)
+        .arg(url)
+        .output()
+        .with_context(|| format!("Could not run {opener}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b"/src/a"), "L3NyYy9h");
+        assert_eq!(base64(b"/src/ab"), "L3NyYy9hYg==");
+        assert_eq!(base64(b"/src/abc"), "L3NyYy9hYmM=");
+    }
+}
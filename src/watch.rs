@@ -0,0 +1,108 @@
+//! Keeps the cache current while repos are cloned, deleted or committed to.
+//!
+//! The scan roots are watched recursively. Changes are collected for a moment so a clone or a
+//! checkout is handled once, then every repo that saw its `.git` appear or disappear, or its HEAD
+//! or branches move, is read again. Each root is read with the scan options of its policy, its
+//! excludes, max depth and whether sizes and status are read.
+
+use crate::{
+    get_cache_from_disk, is_excluded, merge_cache_to_disk, update_repo_data_with, ScanOpts,
+};
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+pub fn watch(roots: &[(PathBuf, ScanOpts)]) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for (root, _) in roots {
+        watcher
+            .watch(root, RecursiveMode::Recursive)
+            .with_context(|| format!("Could not watch {root:?}"))?;
+        println!("Watching {}", root.display());
+    }
+
+    loop {
+        let mut events = vec![rx.recv()?];
+        // let a burst of changes settle
+        thread::sleep(Duration::from_secs(2));
+        events.extend(rx.try_iter());
+
+        let mut repos = BTreeMap::new();
+        for event in events {
+            match event {
+                Ok(event) => repos.extend(event.paths.iter().filter_map(|p| {
+                    // the innermost root when they are nested
+                    let (root, opts) = roots
+                        .iter()
+                        .filter(|(r, _)| p.starts_with(r))
+                        .max_by_key(|(r, _)| r.components().count())?;
+                    let repo = repo_of(p, &opts.exclude)?;
+                    let depth = repo.strip_prefix(root).ok()?.components().count();
+                    let too_deep = opts.max_depth.is_some_and(|max| depth > max);
+                    (!too_deep).then_some((repo, opts))
+                })),
+                Err(err) => eprintln!("Watch error: {err}"),
+            }
+        }
+        if repos.is_empty() {
+            continue;
+        }
+
+        let mut cache = get_cache_from_disk().unwrap_or_default();
+        for (repo, opts) in repos {
+            if repo.join(".git").exists() {
+                update_repo_data_with(&repo, &mut cache, opts)?;
+                println!("Updated {}", repo.display());
+            } else if let Some(i) = cache.iter().position(|e| Path::new(&e.path) == repo) {
+                let base = cache.clone();
+                cache.remove(i);
+                merge_cache_to_disk(&base, &mut cache)?;
+                println!("Dropped {}", repo.display());
+            }
+        }
+    }
+}
+
+/// Work tree of a change we care about: `.git` itself, HEAD or a branch ref.
+fn repo_of(path: &Path, exclude: &[String]) -> Option<PathBuf> {
+    let parts = path
+        .iter()
+        .map(|c| c.to_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    let git = parts.iter().position(|c| *c == ".git")?;
+
+    let relevant = matches!(&parts[git + 1..], [] | ["HEAD"] | ["refs", "heads", ..]);
+    let repo = path.iter().take(git).collect::<PathBuf>();
+    let excluded = repo.ancestors().any(|p| is_excluded(exclude, p));
+
+    (relevant && !excluded).then_some(repo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_of() {
+        let exclude = ["node_modules".to_owned(), "/mnt/*".to_owned()];
+        let repo = |p: &str| repo_of(Path::new(p), &exclude);
+
+        assert_eq!(repo("/src/a/.git"), Some(PathBuf::from("/src/a")));
+        assert_eq!(repo("/src/a/.git/HEAD"), Some(PathBuf::from("/src/a")));
+        assert_eq!(
+            repo("/src/a/.git/refs/heads/main"),
+            Some(PathBuf::from("/src/a"))
+        );
+        assert_eq!(repo("/src/a/.git/objects/ab/cdef"), None);
+        assert_eq!(repo("/src/a/src/main.rs"), None);
+        assert_eq!(repo("/src/a/node_modules/b/.git/HEAD"), None);
+        assert_eq!(repo("/mnt/nas/a/.git/HEAD"), None);
+    }
+}